@@ -1,39 +1,387 @@
-#[derive(Debug, Clone)]
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::ops::Deref;
+
+use super::Error;
+
+/// Contents of a buffer, either allocated or mapped from a file.
+#[derive(Debug)]
+enum Bytes {
+    Heap(Vec<u8>),
+    /// A mapping whose writes reach the file.
+    Mapped(memmap2::MmapMut),
+    /// A mapping of a file opened read-only, which views cannot write to.
+    MappedReadOnly(memmap2::Mmap),
+}
+
+impl Default for Bytes {
+    fn default() -> Self {
+        Bytes::Heap(Vec::new())
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Bytes::Heap(bytes) => bytes,
+            Bytes::Mapped(map) => map,
+            Bytes::MappedReadOnly(map) => map,
+        }
+    }
+}
+
+impl Bytes {
+    /// The contents, unless they are mapped read-only.
+    fn writable(&mut self) -> Option<&mut [u8]> {
+        match self {
+            Bytes::Heap(bytes) => Some(bytes),
+            Bytes::Mapped(map) => Some(map),
+            Bytes::MappedReadOnly(_) => None,
+        }
+    }
+
+    /// Changes the length to `new_len`, copying a mapping into memory unless
+    /// its length is unchanged. Gives the contents back on failure.
+    fn resized(self, new_len: usize) -> Result<Self, (Self, Error)> {
+        match self {
+            Bytes::Heap(mut bytes) => match ArrayBuffer::grow(&mut bytes, new_len) {
+                Ok(()) => Ok(Bytes::Heap(bytes)),
+                Err(err) => Err((Bytes::Heap(bytes), err)),
+            },
+            _ if self.len() == new_len => Ok(self),
+            _ => {
+                let mut bytes = Vec::new();
+                if bytes.try_reserve_exact(new_len).is_err() {
+                    return Err((self, Error::Allocation));
+                }
+                bytes.extend_from_slice(&self[..new_len.min(self.len())]);
+                bytes.resize(new_len, 0);
+                Ok(Bytes::Heap(bytes))
+            }
+        }
+    }
+}
+
+fn io_error(operation: &'static str, err: std::io::Error) -> Error {
+    Error::Io {
+        operation,
+        message: err.to_string(),
+    }
+}
+
+#[derive(Debug, Default)]
+struct Storage {
+    bytes: RefCell<Bytes>,
+    max_len: Option<usize>,
+    detached: Cell<bool>,
+}
+
+/// Reference counted byte storage shared by every view created over it.
+///
+/// The bytes live behind a `RefCell` so that views holding a shared handle can
+/// still write, resize or transfer them; accesses go through
+/// [`ArrayBuffer::read_into`] and [`ArrayBuffer::write_from`], which never hand
+/// out references to the contents, except for the Rust-side views returned by
+/// `TypedArray::as_typed`.
+#[derive(Debug, Clone, Default)]
 pub struct ArrayBuffer {
-    _slice: std::rc::Rc<[u8]>,
+    _storage: std::rc::Rc<Storage>,
+}
+
+fn allocate(size: usize) -> Result<Vec<u8>, Error> {
+    let mut v = Vec::new();
+    v.try_reserve_exact(size).map_err(|_| Error::Allocation)?;
+    v.resize(size, 0);
+    Ok(v)
 }
 
 impl ArrayBuffer {
-    pub fn new(size: usize) -> Result<Self, std::collections::TryReserveError> {
+    pub fn new(size: usize) -> Result<Self, Error> {
+        Ok(Self::from_vec(allocate(size)?, None))
+    }
+
+    /// Copies `bytes` into a new fixed-length buffer with a single `memcpy`;
+    /// this backs `ArrayBuffer.fromString`.
+    ///
+    /// Lua strings are length-counted, so embedded NULs are copied like any
+    /// other byte. A string always fits in memory already, but its copy may
+    /// not: that fails with [`Error::Allocation`] rather than aborting.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
         let mut v = Vec::new();
-        v.try_reserve_exact(size)?;
-        v.resize(size, 0);
-        Ok(Self { _slice: v.into() })
+        v.try_reserve_exact(bytes.len())
+            .map_err(|_| Error::Allocation)?;
+        v.extend_from_slice(bytes);
+        Ok(Self::from_vec(v, None))
     }
 
-    pub fn slice(&self) -> &[u8] {
-        &self._slice
+    /// Creates a resizable buffer that can later grow up to `max_len` bytes.
+    pub fn with_max_len(size: usize, max_len: usize) -> Result<Self, Error> {
+        if size > max_len {
+            return Err(Error::ExceedsMaxLength { len: size, max_len });
+        }
+        Ok(Self::from_vec(allocate(size)?, Some(max_len)))
     }
 
-    pub fn len(&self) -> usize {
-        self._slice.len()
+    /// Maps `length` bytes of the file at `path` starting at the byte
+    /// `offset`, or the rest of the file if `length` is `None`.
+    ///
+    /// With `writable`, writes through any view go to the file, and reach it
+    /// at the latest when the mapping is flushed or unmapped. Otherwise the
+    /// file is opened and mapped read-only, and writing through a view fails
+    /// with [`Error::ReadOnly`].
+    ///
+    /// The buffer has a fixed length, and is unmapped once it is detached or
+    /// the last view over it is collected.
+    ///
+    /// # Safety
+    ///
+    /// Until the buffer is unmapped, the file must not be truncated, which
+    /// makes accesses past its new end raise `SIGBUS`, nor mapped writable by
+    /// another buffer or process, which would alias the contents that views
+    /// borrow.
+    pub unsafe fn mmap(
+        path: &std::path::Path,
+        writable: bool,
+        offset: u64,
+        length: Option<usize>,
+    ) -> Result<Self, Error> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(writable)
+            .open(path)
+            .map_err(|err| io_error("open file", err))?;
+        let file_len = file
+            .metadata()
+            .map_err(|err| io_error("read file metadata", err))?
+            .len();
+        let out_of_bounds = |end: u64| Error::OutOfBounds {
+            target: "file",
+            offset: usize::try_from(end).unwrap_or(usize::MAX),
+            len: usize::try_from(file_len).unwrap_or(usize::MAX),
+        };
+        let available = file_len
+            .checked_sub(offset)
+            .ok_or_else(|| out_of_bounds(offset))?;
+        // Pages past the end of the file cannot be accessed, so the mapping
+        // must not extend beyond it.
+        let length = match length {
+            Some(length) if length as u64 > available => {
+                return Err(out_of_bounds(offset.saturating_add(length as u64)))
+            }
+            Some(length) => length,
+            None => usize::try_from(available).map_err(|_| Error::Allocation)?,
+        };
+        let mut options = memmap2::MmapOptions::new();
+        options.offset(offset).len(length);
+        // SAFETY: the caller keeps the file from being truncated or remapped,
+        // as documented above.
+        let bytes = unsafe {
+            if writable {
+                options.map_mut(&file).map(Bytes::Mapped)
+            } else {
+                options.map(&file).map(Bytes::MappedReadOnly)
+            }
+        }
+        .map_err(|err| io_error("map file", err))?;
+        Ok(Self::from_storage(bytes, None))
     }
-}
 
-impl Default for ArrayBuffer {
-    fn default() -> Self {
-        const ARRAY: [u8; 0] = [];
+    fn from_vec(bytes: Vec<u8>, max_len: Option<usize>) -> Self {
+        Self::from_storage(Bytes::Heap(bytes), max_len)
+    }
+
+    fn from_storage(bytes: Bytes, max_len: Option<usize>) -> Self {
         Self {
-            _slice: std::rc::Rc::from(ARRAY.as_slice()),
+            _storage: std::rc::Rc::new(Storage {
+                bytes: RefCell::new(bytes),
+                max_len,
+                detached: Cell::new(false),
+            }),
+        }
+    }
+
+    /// Copies `dest.len()` bytes starting at `offset` into `dest`.
+    ///
+    /// Returns `None` without touching `dest` if the range is out of bounds.
+    pub fn read_into(&self, offset: usize, dest: &mut [u8]) -> Option<()> {
+        let bytes = self._storage.bytes.borrow();
+        dest.copy_from_slice(bytes.get(offset..offset.checked_add(dest.len())?)?);
+        Some(())
+    }
+
+    /// Copies `src` into the buffer starting at `offset`.
+    ///
+    /// Returns `None` without writing anything if the range is out of bounds
+    /// or the buffer is read-only, see [`ArrayBuffer::check_writable`].
+    pub fn write_from(&self, offset: usize, src: &[u8]) -> Option<()> {
+        let mut bytes = self._storage.bytes.borrow_mut();
+        bytes
+            .writable()?
+            .get_mut(offset..offset.checked_add(src.len())?)?
+            .copy_from_slice(src);
+        Some(())
+    }
+
+    /// Borrows the contents; writing, resizing or detaching the buffer panics
+    /// until the borrow is released.
+    pub(super) fn bytes(&self) -> Ref<'_, [u8]> {
+        Ref::map(self._storage.bytes.borrow(), |bytes| &**bytes)
+    }
+
+    /// Mutably borrows the contents; any other access to the buffer panics
+    /// until the borrow is released.
+    pub(super) fn bytes_mut(&self) -> Result<RefMut<'_, [u8]>, Error> {
+        RefMut::filter_map(self._storage.bytes.borrow_mut(), Bytes::writable)
+            .map_err(|_| Error::ReadOnly)
+    }
+
+    /// Whether both handles refer to the same storage.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        std::rc::Rc::ptr_eq(&self._storage, &other._storage)
+    }
+
+    pub fn len(&self) -> usize {
+        self._storage.bytes.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Upper bound for [`ArrayBuffer::resize`]; the current length for
+    /// fixed-length buffers.
+    pub fn max_len(&self) -> usize {
+        self._storage.max_len.unwrap_or_else(|| self.len())
+    }
+
+    pub fn is_resizable(&self) -> bool {
+        self._storage.max_len.is_some()
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self._storage.detached.get()
+    }
+
+    /// Fails with [`Error::Detached`] if the buffer has been detached.
+    pub fn check_attached(&self) -> Result<(), Error> {
+        if self.is_detached() {
+            Err(Error::Detached)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Like [`ArrayBuffer::check_attached`], but also fails with
+    /// [`Error::ReadOnly`] if the buffer is mapped read-only.
+    pub fn check_writable(&self) -> Result<(), Error> {
+        self.check_attached()?;
+        match &*self._storage.bytes.borrow() {
+            Bytes::MappedReadOnly(_) => Err(Error::ReadOnly),
+            _ => Ok(()),
+        }
+    }
+
+    /// Copies the bytes `begin..=end` into a new fixed-length buffer.
+    ///
+    /// Bounds are 1-based and negative values count from the end, see
+    /// `relative_range`.
+    pub fn slice(
+        &self,
+        begin: Option<mlua::Integer>,
+        end: Option<mlua::Integer>,
+    ) -> Result<Self, Error> {
+        let bytes = self._storage.bytes.borrow();
+        let range = super::relative_range(bytes.len(), begin, end);
+        let mut v = Vec::new();
+        v.try_reserve_exact(range.len())
+            .map_err(|_| Error::Allocation)?;
+        v.extend_from_slice(&bytes[range]);
+        Ok(Self::from_vec(v, None))
+    }
+
+    /// Writes the changes made to a buffer mapped writable back to its
+    /// file, returning once they are on disk.
+    pub fn flush(&self) -> Result<(), Error> {
+        self.check_attached()?;
+        match &*self._storage.bytes.borrow() {
+            Bytes::Mapped(map) => map.flush().map_err(|err| io_error("flush mapping", err)),
+            _ => Err(Error::NotFlushable),
         }
     }
+
+    /// Releases the memory of the buffer, unmapping it if it is mapped; every
+    /// view over it becomes empty.
+    pub fn detach(&self) {
+        self._storage.detached.set(true);
+        drop(self._storage.bytes.take());
+    }
+
+    fn check_len(&self, new_len: usize) -> Result<(), Error> {
+        match self._storage.max_len {
+            Some(max_len) if new_len > max_len => Err(Error::ExceedsMaxLength {
+                len: new_len,
+                max_len,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn grow(bytes: &mut Vec<u8>, new_len: usize) -> Result<(), Error> {
+        bytes
+            .try_reserve_exact(new_len.saturating_sub(bytes.len()))
+            .map_err(|_| Error::Allocation)?;
+        bytes.resize(new_len, 0);
+        Ok(())
+    }
+
+    /// Changes the length of a resizable buffer, zero-filling new bytes.
+    pub fn resize(&self, new_len: usize) -> Result<(), Error> {
+        if !self.is_resizable() {
+            return Err(Error::NotResizable);
+        }
+        self.check_attached()?;
+        self.check_len(new_len)?;
+        match &mut *self._storage.bytes.borrow_mut() {
+            Bytes::Heap(bytes) => Self::grow(bytes, new_len),
+            // Mappings always back fixed-length buffers.
+            Bytes::Mapped(_) | Bytes::MappedReadOnly(_) => Err(Error::NotResizable),
+        }
+    }
+
+    /// Moves the contents into a new buffer of `new_len` bytes (the current
+    /// length by default) and detaches this one.
+    ///
+    /// The allocation or mapping is reused, so transferring without changing
+    /// the length never copies. A mapping transferred to another length is
+    /// copied into memory.
+    pub fn transfer(&self, new_len: Option<usize>) -> Result<ArrayBuffer, Error> {
+        self.check_attached()?;
+        let new_len = new_len.unwrap_or_else(|| self.len());
+        self.check_len(new_len)?;
+        let bytes = match self._storage.bytes.take().resized(new_len) {
+            Ok(bytes) => bytes,
+            Err((bytes, err)) => {
+                self._storage.bytes.replace(bytes);
+                return Err(err);
+            }
+        };
+        self._storage.detached.set(true);
+        Ok(Self::from_storage(bytes, self._storage.max_len))
+    }
+}
+
+impl From<Vec<u8>> for ArrayBuffer {
+    /// Wraps `bytes` in a fixed-length buffer without copying them.
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from_vec(bytes, None)
+    }
 }
 
 impl std::fmt::Display for ArrayBuffer {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         fmt.write_str("ArrayBuffer { ")?;
-        use std::ops::Deref;
-        for v in self._slice.deref() {
+        for v in self._storage.bytes.borrow().iter() {
             write!(fmt, "{}, ", v)?;
         }
         fmt.write_str(" }")
@@ -41,6 +389,13 @@ impl std::fmt::Display for ArrayBuffer {
 }
 
 impl mlua::UserData for ArrayBuffer {
+    fn add_fields<'lua, F: mlua::UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("byteLength", |_, this| Ok(this.len()));
+        fields.add_field_method_get("maxByteLength", |_, this| Ok(this.max_len()));
+        fields.add_field_method_get("resizable", |_, this| Ok(this.is_resizable()));
+        fields.add_field_method_get("detached", |_, this| Ok(this.is_detached()));
+    }
+
     fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
         methods.add_meta_function(
             mlua::MetaMethod::ToString,
@@ -50,5 +405,43 @@ impl mlua::UserData for ArrayBuffer {
             mlua::MetaMethod::Len,
             |_, this: ArrayBuffer| -> Result<usize, _> { Ok(this.len()) },
         );
+        methods.add_meta_function(
+            mlua::MetaMethod::Eq,
+            |_, (lhs, rhs): (mlua::AnyUserData, mlua::AnyUserData)| {
+                Ok(match (lhs.borrow::<Self>(), rhs.borrow::<Self>()) {
+                    (Ok(lhs), Ok(rhs)) => lhs.ptr_eq(&rhs),
+                    _ => false,
+                })
+            },
+        );
+        methods.add_method(
+            "resize",
+            |_, this, new_len: usize| Ok(this.resize(new_len)?),
+        );
+        methods.add_method(
+            "slice",
+            |_, this, args: (Option<mlua::Integer>, Option<mlua::Integer>)| {
+                let (begin, end) = args;
+                this.check_attached()?;
+                Ok(this.slice(begin, end)?)
+            },
+        );
+        methods.add_method(
+            "toString",
+            |lua, this, args: (Option<usize>, Option<usize>)| {
+                let (offset, length) = args;
+                this.check_attached()?;
+                let range = super::byte_range("ArrayBuffer", this.len(), offset, length)?;
+                super::ArrayBufferLike::from(this.clone()).to_lua_string(lua, range)
+            },
+        );
+        methods.add_method("flush", |_, this, ()| Ok(this.flush()?));
+        methods.add_method("detach", |_, this, ()| {
+            this.detach();
+            Ok(())
+        });
+        methods.add_method("transfer", |_, this, new_len: Option<usize>| {
+            Ok(this.transfer(new_len)?)
+        });
     }
 }
@@ -0,0 +1,140 @@
+use super::{ArrayBuffer, Error, SharedArrayBuffer};
+
+/// Backing store of a view, either a thread-local [`ArrayBuffer`] or a
+/// [`SharedArrayBuffer`].
+#[derive(Debug, Clone)]
+pub enum ArrayBufferLike {
+    ArrayBuffer(ArrayBuffer),
+    SharedArrayBuffer(SharedArrayBuffer),
+}
+
+impl ArrayBufferLike {
+    pub fn read_into(&self, offset: usize, dest: &mut [u8]) -> Option<()> {
+        match self {
+            ArrayBufferLike::ArrayBuffer(buffer) => buffer.read_into(offset, dest),
+            ArrayBufferLike::SharedArrayBuffer(buffer) => buffer.read_into(offset, dest),
+        }
+    }
+
+    pub fn write_from(&self, offset: usize, src: &[u8]) -> Option<()> {
+        match self {
+            ArrayBufferLike::ArrayBuffer(buffer) => buffer.write_from(offset, src),
+            ArrayBufferLike::SharedArrayBuffer(buffer) => buffer.write_from(offset, src),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ArrayBufferLike::ArrayBuffer(buffer) => buffer.len(),
+            ArrayBufferLike::SharedArrayBuffer(buffer) => buffer.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_resizable(&self) -> bool {
+        match self {
+            ArrayBufferLike::ArrayBuffer(buffer) => buffer.is_resizable(),
+            ArrayBufferLike::SharedArrayBuffer(_) => false,
+        }
+    }
+
+    pub fn is_detached(&self) -> bool {
+        match self {
+            ArrayBufferLike::ArrayBuffer(buffer) => buffer.is_detached(),
+            ArrayBufferLike::SharedArrayBuffer(_) => false,
+        }
+    }
+
+    /// Fails with [`Error::Detached`] if the buffer has been detached.
+    pub fn check_attached(&self) -> Result<(), Error> {
+        match self {
+            ArrayBufferLike::ArrayBuffer(buffer) => buffer.check_attached(),
+            ArrayBufferLike::SharedArrayBuffer(_) => Ok(()),
+        }
+    }
+
+    /// Fails with [`Error::Detached`] if the buffer has been detached, or
+    /// with [`Error::ReadOnly`] if it is mapped read-only.
+    pub fn check_writable(&self) -> Result<(), Error> {
+        match self {
+            ArrayBufferLike::ArrayBuffer(buffer) => buffer.check_writable(),
+            ArrayBufferLike::SharedArrayBuffer(_) => Ok(()),
+        }
+    }
+
+    /// Copies the bytes in `range` into a Lua string, failing if `range` is
+    /// past the end of the buffer.
+    ///
+    /// The bytes are copied out before the string is created: allocating it
+    /// may run finalizers that write to this very buffer.
+    pub(super) fn to_lua_string<'lua>(
+        &self,
+        lua: &'lua mlua::Lua,
+        range: std::ops::Range<usize>,
+    ) -> mlua::Result<mlua::String<'lua>> {
+        let mut bytes = vec![0; range.len()];
+        self.read_into(range.start, &mut bytes)
+            .ok_or(Error::OutOfBounds {
+                target: if self.is_shared() {
+                    "SharedArrayBuffer"
+                } else {
+                    "ArrayBuffer"
+                },
+                offset: range.end,
+                len: self.len(),
+            })?;
+        lua.create_string(&bytes)
+    }
+
+    pub fn is_shared(&self) -> bool {
+        matches!(self, ArrayBufferLike::SharedArrayBuffer(_))
+    }
+}
+
+impl Default for ArrayBufferLike {
+    fn default() -> Self {
+        ArrayBufferLike::ArrayBuffer(ArrayBuffer::default())
+    }
+}
+
+impl From<ArrayBuffer> for ArrayBufferLike {
+    fn from(buffer: ArrayBuffer) -> Self {
+        ArrayBufferLike::ArrayBuffer(buffer)
+    }
+}
+
+impl From<SharedArrayBuffer> for ArrayBufferLike {
+    fn from(buffer: SharedArrayBuffer) -> Self {
+        ArrayBufferLike::SharedArrayBuffer(buffer)
+    }
+}
+
+impl<'lua> mlua::FromLua<'lua> for ArrayBufferLike {
+    fn from_lua(value: mlua::Value<'lua>, _: &'lua mlua::Lua) -> mlua::Result<Self> {
+        match &value {
+            mlua::Value::UserData(ud) if ud.is::<ArrayBuffer>() => {
+                Ok(ud.borrow::<ArrayBuffer>()?.clone().into())
+            }
+            mlua::Value::UserData(ud) if ud.is::<SharedArrayBuffer>() => {
+                Ok(ud.borrow::<SharedArrayBuffer>()?.clone().into())
+            }
+            _ => Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ArrayBuffer",
+                message: Some("expected an ArrayBuffer or a SharedArrayBuffer".into()),
+            }),
+        }
+    }
+}
+
+impl<'lua> mlua::ToLua<'lua> for ArrayBufferLike {
+    fn to_lua(self, lua: &'lua mlua::Lua) -> mlua::Result<mlua::Value<'lua>> {
+        match self {
+            ArrayBufferLike::ArrayBuffer(buffer) => buffer.to_lua(lua),
+            ArrayBufferLike::SharedArrayBuffer(buffer) => buffer.to_lua(lua),
+        }
+    }
+}
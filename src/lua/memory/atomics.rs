@@ -0,0 +1,325 @@
+use std::sync::atomic::Ordering;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use super::shared_array_buffer::AtomicElement;
+use super::typed_array::{
+    Numeric, OverflowPolicy, TypedArrayElement, TypedArrayKind, TypedArrayVariant,
+};
+use super::{ArrayBufferLike, Error, TypedArray};
+
+/// Threads blocked in `Atomics.wait`, keyed by the address they wait on.
+///
+/// A single lock protects the whole list so that the value check in `wait`
+/// and the dequeue in `notify` cannot interleave.
+struct WaiterList {
+    next_id: u64,
+    waiting: Vec<(usize, u64)>,
+}
+
+static WAITERS: Mutex<WaiterList> = Mutex::new(WaiterList {
+    next_id: 0,
+    waiting: Vec::new(),
+});
+static WAKE: Condvar = Condvar::new();
+
+/// An element of an integer typed array that atomics can operate on.
+struct Location {
+    kind: TypedArrayKind,
+    overflow: OverflowPolicy,
+    buffer: ArrayBufferLike,
+    byte_index: usize,
+}
+
+impl Location {
+    fn new(array: &TypedArray, index: usize) -> mlua::Result<Self> {
+        array.buffer().check_attached()?;
+        match array.kind() {
+            TypedArrayKind::SInt32
+            | TypedArrayKind::UInt32
+            | TypedArrayKind::SInt64
+            | TypedArrayKind::UInt64 => {}
+            kind => {
+                return Err(Error::KindMismatch {
+                    expected: "an Int32, UInt32, Int64 or UInt64 array",
+                    found: kind.name(),
+                }
+                .into())
+            }
+        }
+        let index = index
+            .checked_sub(1)
+            .filter(|index| *index < array.len())
+            .ok_or(Error::OutOfBounds {
+                target: array.name(),
+                offset: index,
+                len: array.len(),
+            })?;
+        Ok(Location {
+            kind: array.kind(),
+            overflow: array.overflow_policy(),
+            buffer: array.buffer(),
+            byte_index: array.byte_offset() + index * array.kind().bytes_per_element(),
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.kind.bytes_per_element()
+    }
+
+    /// Converts `value` to the element type following the overflow policy of
+    /// the array, and returns its bits.
+    fn to_bits(&self, value: Numeric) -> Result<u64, Error> {
+        let (min, max): (i128, i128) = match self.kind {
+            TypedArrayKind::SInt32 => (i32::MIN.into(), i32::MAX.into()),
+            TypedArrayKind::UInt32 => (0, u32::MAX.into()),
+            TypedArrayKind::SInt64 => (i64::MIN.into(), i64::MAX.into()),
+            _ => (0, u64::MAX.into()),
+        };
+        let wide = value
+            .to_integer(min, max, self.overflow)
+            .ok_or(Error::Overflow {
+                target: self.kind.name(),
+            })?;
+        Ok(if self.size() == 8 {
+            wide as u64
+        } else {
+            wide as u32 as u64
+        })
+    }
+
+    fn to_variant(&self, bits: u64) -> TypedArrayVariant {
+        match self.kind {
+            TypedArrayKind::SInt32 => TypedArrayVariant::SInt32(bits as u32 as i32),
+            TypedArrayKind::UInt32 => TypedArrayVariant::UInt32(bits as u32),
+            TypedArrayKind::SInt64 => TypedArrayVariant::SInt64(bits as i64),
+            _ => TypedArrayVariant::UInt64(bits),
+        }
+    }
+
+    fn misaligned(&self) -> Error {
+        Error::Misaligned {
+            target: self.kind.name(),
+            what: "byte offset",
+            value: self.byte_index,
+            alignment: self.size(),
+            argument: Some(2),
+        }
+    }
+
+    /// The element within a shared buffer; `None` for non-shared buffers.
+    fn atomic(&self) -> Result<Option<AtomicElement<'_>>, Error> {
+        match &self.buffer {
+            ArrayBufferLike::SharedArrayBuffer(buffer) => buffer
+                .atomic(self.byte_index, self.size())
+                .map(Some)
+                .ok_or_else(|| self.misaligned()),
+            ArrayBufferLike::ArrayBuffer(_) => Ok(None),
+        }
+    }
+
+    fn read(&self) -> Result<u64, Error> {
+        if let Some(atomic) = self.atomic()? {
+            return Ok(atomic.load(Ordering::SeqCst));
+        }
+        if self.size() == 8 {
+            <u64 as TypedArrayElement>::get(&self.buffer, self.byte_index, 0)
+        } else {
+            <u32 as TypedArrayElement>::get(&self.buffer, self.byte_index, 0).map(u64::from)
+        }
+        .ok_or_else(|| self.misaligned())
+    }
+
+    fn write(&self, bits: u64) -> Result<(), Error> {
+        if let Some(atomic) = self.atomic()? {
+            atomic.store(bits, Ordering::SeqCst);
+            return Ok(());
+        }
+        self.buffer.check_writable()?;
+        if self.size() == 8 {
+            <u64 as TypedArrayElement>::set(&self.buffer, self.byte_index, 0, bits)
+        } else {
+            <u32 as TypedArrayElement>::set(&self.buffer, self.byte_index, 0, bits as u32)
+        }
+        .ok_or_else(|| self.misaligned())
+    }
+
+    /// Atomically replaces the element with `f(old)` and returns `old`.
+    fn update(&self, f: impl Fn(u64) -> u64) -> Result<u64, Error> {
+        match self.atomic()? {
+            Some(atomic) => Ok(atomic.update(f, Ordering::SeqCst)),
+            // Non-shared buffers are only reachable from a single thread.
+            None => {
+                let old = self.read()?;
+                self.write(f(old))?;
+                Ok(old)
+            }
+        }
+    }
+
+    /// Identifies the element across every view and Lua state.
+    fn address(&self) -> Result<Option<usize>, Error> {
+        Ok(self.atomic()?.map(|atomic| atomic.address()))
+    }
+}
+
+macro_rules! add_operation {
+    ($lua:ident, $table:ident, $name:literal, $op:expr) => {
+        $table.raw_set(
+            $name,
+            $lua.create_function(
+                |_, args: (TypedArray, usize, Numeric)| -> Result<TypedArrayVariant, _> {
+                    let (array, index, value) = args;
+                    let location = Location::new(&array, index)?;
+                    let value = location.to_bits(value)?;
+                    let op: fn(u64, u64) -> u64 = $op;
+                    let old = location.update(|old| op(old, value))?;
+                    Ok(location.to_variant(old))
+                },
+            )?,
+        )?;
+    };
+}
+
+fn wait(
+    array: TypedArray,
+    index: usize,
+    value: Numeric,
+    timeout: Option<f64>,
+) -> mlua::Result<&'static str> {
+    if !matches!(
+        array.kind(),
+        TypedArrayKind::SInt32 | TypedArrayKind::SInt64
+    ) {
+        return Err(Error::KindMismatch {
+            expected: "an Int32 or Int64 array",
+            found: array.name(),
+        }
+        .into());
+    }
+    let location = Location::new(&array, index)?;
+    let address = location.address()?.ok_or(Error::KindMismatch {
+        expected: "a shared typed array",
+        found: array.name(),
+    })?;
+    let expected = location.to_bits(value)?;
+    let deadline = timeout
+        .filter(|ms| !ms.is_nan())
+        .and_then(|ms| Duration::try_from_secs_f64(ms.max(0.0) / 1000.0).ok())
+        .and_then(|timeout| Instant::now().checked_add(timeout));
+
+    let mut waiters = WAITERS.lock().unwrap();
+    if location.read()? != expected {
+        return Ok("not-equal");
+    }
+    let id = waiters.next_id;
+    waiters.next_id += 1;
+    waiters.waiting.push((address, id));
+    loop {
+        if !waiters.waiting.iter().any(|&(_, waiter)| waiter == id) {
+            return Ok("ok");
+        }
+        waiters = match deadline {
+            None => WAKE.wait(waiters).unwrap(),
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    waiters.waiting.retain(|&(_, waiter)| waiter != id);
+                    return Ok("timed-out");
+                }
+                WAKE.wait_timeout(waiters, deadline - now).unwrap().0
+            }
+        };
+    }
+}
+
+fn notify(array: TypedArray, index: usize, count: Option<f64>) -> mlua::Result<usize> {
+    let location = Location::new(&array, index)?;
+    let address = match location.address()? {
+        Some(address) => address,
+        None => return Ok(0),
+    };
+    let mut count = count.map_or(usize::MAX, |count| count.max(0.0) as usize);
+
+    let mut waiters = WAITERS.lock().unwrap();
+    let mut woken = 0;
+    waiters.waiting.retain(|&(waiting, _)| {
+        if waiting == address && count > 0 {
+            count -= 1;
+            woken += 1;
+            false
+        } else {
+            true
+        }
+    });
+    if woken > 0 {
+        WAKE.notify_all();
+    }
+    Ok(woken)
+}
+
+pub fn create_table<'lua>(lua: &'lua mlua::Lua) -> mlua::Result<mlua::Table<'lua>> {
+    let atomics_table = lua.create_table()?;
+
+    atomics_table.raw_set(
+        "load",
+        lua.create_function(
+            |_, args: (TypedArray, usize)| -> Result<TypedArrayVariant, _> {
+                let (array, index) = args;
+                let location = Location::new(&array, index)?;
+                Ok(location.to_variant(location.read()?))
+            },
+        )?,
+    )?;
+    atomics_table.raw_set(
+        "store",
+        lua.create_function(
+            |_, args: (TypedArray, usize, Numeric)| -> Result<TypedArrayVariant, _> {
+                let (array, index, value) = args;
+                let location = Location::new(&array, index)?;
+                let bits = location.to_bits(value)?;
+                location.write(bits)?;
+                Ok(location.to_variant(bits))
+            },
+        )?,
+    )?;
+
+    add_operation!(lua, atomics_table, "add", |old, value| old
+        .wrapping_add(value));
+    add_operation!(lua, atomics_table, "sub", |old, value| old
+        .wrapping_sub(value));
+    add_operation!(lua, atomics_table, "and", |old, value| old & value);
+    add_operation!(lua, atomics_table, "or", |old, value| old | value);
+    add_operation!(lua, atomics_table, "xor", |old, value| old ^ value);
+    add_operation!(lua, atomics_table, "exchange", |_, value| value);
+
+    atomics_table.raw_set(
+        "compareExchange",
+        lua.create_function(
+            |_, args: (TypedArray, usize, Numeric, Numeric)| -> Result<TypedArrayVariant, _> {
+                let (array, index, expected, replacement) = args;
+                let location = Location::new(&array, index)?;
+                let expected = location.to_bits(expected)?;
+                let replacement = location.to_bits(replacement)?;
+                let old = location.update(|old| if old == expected { replacement } else { old })?;
+                Ok(location.to_variant(old))
+            },
+        )?,
+    )?;
+    atomics_table.raw_set(
+        "wait",
+        lua.create_function(|_, args: (TypedArray, usize, Numeric, Option<f64>)| {
+            let (array, index, value, timeout) = args;
+            wait(array, index, value, timeout)
+        })?,
+    )?;
+    atomics_table.raw_set(
+        "notify",
+        lua.create_function(|_, args: (TypedArray, usize, Option<f64>)| {
+            let (array, index, count) = args;
+            notify(array, index, count)
+        })?,
+    )?;
+
+    Ok(atomics_table)
+}
@@ -0,0 +1,300 @@
+// Base64 and hexadecimal conversions of `UInt8Array`, following the TC39
+// `Uint8Array` base64 proposal.
+
+use super::typed_array::TypedArrayKind;
+use super::{ArrayBuffer, Error, TypedArray};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alphabet {
+    #[default]
+    Base64,
+    Base64Url,
+}
+
+/// What to do with a final chunk of fewer than 4 characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LastChunkHandling {
+    /// Decode it, padded or not, ignoring non-zero padding bits.
+    #[default]
+    Loose,
+    /// Require padding and zero padding bits.
+    Strict,
+    /// Leave it undecoded, for inputs that arrive in pieces.
+    StopBeforePartial,
+}
+
+/// Options accepted by `toBase64`, `fromBase64` and `setFromBase64`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Options {
+    pub alphabet: Alphabet,
+    pub omit_padding: bool,
+    pub last_chunk_handling: LastChunkHandling,
+}
+
+impl Options {
+    /// Reads the `alphabet`, `omitPadding` and `lastChunkHandling` fields of
+    /// an options table.
+    pub fn from_table(options: Option<mlua::Table>) -> mlua::Result<Self> {
+        let options = match options {
+            Some(options) => options,
+            None => return Ok(Options::default()),
+        };
+        let invalid = |name: &str, expected: &str| Error::Argument {
+            kind: super::ErrorKind::Type,
+            argument: None,
+            message: format!("invalid option '{}', expected {}", name, expected),
+        };
+        let alphabet = match options.get::<_, Option<String>>("alphabet")?.as_deref() {
+            None | Some("base64") => Alphabet::Base64,
+            Some("base64url") => Alphabet::Base64Url,
+            Some(_) => return Err(invalid("alphabet", "'base64' or 'base64url'").into()),
+        };
+        let last_chunk_handling = match options
+            .get::<_, Option<String>>("lastChunkHandling")?
+            .as_deref()
+        {
+            None | Some("loose") => LastChunkHandling::Loose,
+            Some("strict") => LastChunkHandling::Strict,
+            Some("stop-before-partial") => LastChunkHandling::StopBeforePartial,
+            Some(_) => {
+                return Err(invalid(
+                    "lastChunkHandling",
+                    "'loose', 'strict' or 'stop-before-partial'",
+                )
+                .into())
+            }
+        };
+        Ok(Options {
+            alphabet,
+            omit_padding: options
+                .get::<_, Option<bool>>("omitPadding")?
+                .unwrap_or(false),
+            last_chunk_handling,
+        })
+    }
+}
+
+pub fn encode(bytes: &[u8], options: Options) -> String {
+    let table = match options.alphabet {
+        Alphabet::Base64 => BASE64,
+        Alphabet::Base64Url => BASE64_URL,
+    };
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            out.push(table[(bits >> (18 - 6 * i)) as usize & 63] as char);
+        }
+        if !options.omit_padding {
+            for _ in chunk.len()..3 {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn encode_hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        out.push(DIGITS[usize::from(byte >> 4)] as char);
+        out.push(DIGITS[usize::from(byte & 15)] as char);
+    }
+    out
+}
+
+/// Outcome of a decoding, which may fail after some bytes were decoded.
+#[derive(Debug)]
+pub struct Decoded {
+    /// Number of input characters consumed by `bytes`.
+    pub read: usize,
+    pub bytes: Vec<u8>,
+    pub error: Option<Error>,
+}
+
+impl Decoded {
+    /// Wraps the bytes in a new `UInt8Array`, unless decoding failed.
+    pub fn into_array(self) -> Result<TypedArray, Error> {
+        match self.error {
+            Some(err) => Err(err),
+            None => {
+                TypedArray::with_buffer(TypedArrayKind::UInt8, ArrayBuffer::from(self.bytes).into())
+            }
+        }
+    }
+
+    /// Writes the bytes at the start of `array`, then reports the error if
+    /// decoding failed, or how many characters were read and bytes written.
+    pub fn write_into(self, array: &TypedArray) -> Result<(usize, usize), Error> {
+        array.buffer().write_from(array.byte_offset(), &self.bytes);
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok((self.read, self.bytes.len())),
+        }
+    }
+}
+
+fn value(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+fn skip_whitespace(input: &[u8], mut index: usize) -> usize {
+    while index < input.len() && matches!(input[index], b'\t' | b'\n' | b'\x0C' | b'\r' | b' ') {
+        index += 1;
+    }
+    index
+}
+
+/// Decodes a chunk of 2 to 4 sextets, failing if `strict` and the padding
+/// bits of a partial chunk are not zero.
+fn decode_chunk(chunk: &[u8], strict: bool, bytes: &mut Vec<u8>) -> Option<()> {
+    let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, sextet)| {
+        bits | u32::from(*sextet) << (18 - 6 * i)
+    });
+    let decoded = &bits.to_be_bytes()[1..chunk.len()];
+    let extra = bits.to_be_bytes()[chunk.len()..]
+        .iter()
+        .any(|byte| *byte != 0);
+    if strict && chunk.len() < 4 && extra {
+        return None;
+    }
+    bytes.extend_from_slice(decoded);
+    Some(())
+}
+
+/// Decodes up to `max_len` bytes of base64, skipping ASCII whitespace.
+///
+/// Decoding only ever stops between complete chunks, so `read` never counts
+/// half a chunk.
+pub fn decode(input: &[u8], options: Options, max_len: usize) -> Decoded {
+    let mut decoded = Decoded {
+        read: 0,
+        bytes: Vec::new(),
+        error: None,
+    };
+    if max_len == 0 {
+        return decoded;
+    }
+    let fail = |mut decoded: Decoded, offset: usize| {
+        decoded.error = Some(Error::Syntax {
+            format: "base64",
+            offset,
+        });
+        decoded
+    };
+    let mut chunk = Vec::with_capacity(4);
+    let mut index = 0;
+    loop {
+        index = skip_whitespace(input, index);
+        if index == input.len() {
+            if !chunk.is_empty() {
+                match options.last_chunk_handling {
+                    LastChunkHandling::StopBeforePartial => return decoded,
+                    LastChunkHandling::Loose if chunk.len() > 1 => {
+                        decode_chunk(&chunk, false, &mut decoded.bytes);
+                    }
+                    _ => return fail(decoded, index),
+                }
+            }
+            decoded.read = input.len();
+            return decoded;
+        }
+        let mut c = input[index];
+        index += 1;
+        if c == b'=' {
+            if chunk.len() < 2 {
+                return fail(decoded, index - 1);
+            }
+            index = skip_whitespace(input, index);
+            if chunk.len() == 2 {
+                if index == input.len() {
+                    return match options.last_chunk_handling {
+                        LastChunkHandling::StopBeforePartial => decoded,
+                        _ => fail(decoded, index),
+                    };
+                }
+                if input[index] == b'=' {
+                    index = skip_whitespace(input, index + 1);
+                }
+            }
+            if index < input.len() {
+                return fail(decoded, index);
+            }
+            let strict = options.last_chunk_handling == LastChunkHandling::Strict;
+            if decode_chunk(&chunk, strict, &mut decoded.bytes).is_none() {
+                return fail(decoded, index);
+            }
+            decoded.read = input.len();
+            return decoded;
+        }
+        if options.alphabet == Alphabet::Base64Url {
+            c = match c {
+                b'+' | b'/' => return fail(decoded, index - 1),
+                b'-' => b'+',
+                b'_' => b'/',
+                c => c,
+            };
+        }
+        let sextet = match value(c) {
+            Some(sextet) => sextet,
+            None => return fail(decoded, index - 1),
+        };
+        // Stop before a chunk whose bytes would not all fit.
+        let remaining = max_len - decoded.bytes.len();
+        if (remaining == 1 && chunk.len() == 2) || (remaining == 2 && chunk.len() == 3) {
+            return decoded;
+        }
+        chunk.push(sextet);
+        if chunk.len() == 4 {
+            decode_chunk(&chunk, false, &mut decoded.bytes);
+            chunk.clear();
+            decoded.read = index;
+            if decoded.bytes.len() == max_len {
+                return decoded;
+            }
+        }
+    }
+}
+
+/// Decodes up to `max_len` bytes of hexadecimal digits in either case.
+pub fn decode_hex(input: &[u8], max_len: usize) -> Decoded {
+    let mut decoded = Decoded {
+        read: 0,
+        bytes: Vec::new(),
+        error: None,
+    };
+    let fail = |mut decoded: Decoded, offset: usize| {
+        decoded.error = Some(Error::Syntax {
+            format: "hex",
+            offset,
+        });
+        decoded
+    };
+    if !input.len().is_multiple_of(2) {
+        return fail(decoded, input.len() - 1);
+    }
+    let digit = |c: u8| (c as char).to_digit(16).map(|digit| digit as u8);
+    while decoded.read < input.len() && decoded.bytes.len() < max_len {
+        let read = decoded.read;
+        match (digit(input[read]), digit(input[read + 1])) {
+            (Some(high), Some(low)) => decoded.bytes.push(high << 4 | low),
+            (None, _) => return fail(decoded, read),
+            (_, None) => return fail(decoded, read + 1),
+        }
+        decoded.read += 2;
+    }
+    decoded
+}
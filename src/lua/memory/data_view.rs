@@ -0,0 +1,227 @@
+use super::typed_array::{Numeric, OverflowPolicy, TypedArrayElement, TypedArrayVariant};
+use super::Error;
+
+#[derive(Debug, Clone)]
+pub struct DataView {
+    _buffer: super::ArrayBufferLike,
+    _offset: usize,
+    /// `None` for views over a resizable buffer that track its length.
+    _length: Option<usize>,
+    _overflow: OverflowPolicy,
+}
+
+impl DataView {
+    pub fn new(
+        buffer: super::ArrayBufferLike,
+        offset: Option<usize>,
+        length: Option<usize>,
+    ) -> Result<Self, Error> {
+        buffer.check_attached()?;
+        let offset = offset.unwrap_or(0);
+        let available = buffer.len().checked_sub(offset).ok_or(Error::OutOfBounds {
+            target: "ArrayBuffer",
+            offset,
+            len: buffer.len(),
+        })?;
+        if length.is_none() && buffer.is_resizable() {
+            return Ok(DataView {
+                _buffer: buffer,
+                _offset: offset,
+                _length: None,
+                _overflow: OverflowPolicy::default(),
+            });
+        }
+        let length = length.unwrap_or(available);
+        if length > available {
+            Err(Error::OutOfBounds {
+                target: "ArrayBuffer",
+                offset: offset.saturating_add(length),
+                len: buffer.len(),
+            })
+        } else {
+            Ok(DataView {
+                _buffer: buffer,
+                _offset: offset,
+                _length: Some(length),
+                _overflow: OverflowPolicy::default(),
+            })
+        }
+    }
+
+    /// Byte offset into the buffer, or 0 once the buffer is detached.
+    pub fn byte_offset(&self) -> usize {
+        if self._buffer.is_detached() {
+            0
+        } else {
+            self._offset
+        }
+    }
+
+    pub fn byte_len(&self) -> usize {
+        let available = self._buffer.len().saturating_sub(self._offset);
+        match self._length {
+            Some(length) if length <= available => length,
+            Some(_) => 0,
+            None => available,
+        }
+    }
+
+    pub fn buffer(&self) -> super::ArrayBufferLike {
+        self._buffer.clone()
+    }
+
+    /// How the integer setters store values that do not fit, wrapping like
+    /// JS by default.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self._overflow
+    }
+
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self._overflow = policy;
+    }
+
+    fn byte_index(&self, offset: usize, size: usize) -> Result<usize, Error> {
+        self._buffer.check_attached()?;
+        let len = self.byte_len();
+        match offset.checked_add(size) {
+            Some(end) if end <= len => Ok(self._offset + offset),
+            _ => Err(Error::OutOfBounds {
+                target: "DataView",
+                offset,
+                len,
+            }),
+        }
+    }
+
+    /// Reads `N` bytes at `offset`, failing if they are not all inside the
+    /// view.
+    pub fn get_bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N], Error> {
+        let index = self.byte_index(offset, N)?;
+        let mut bytes = [0; N];
+        self._buffer
+            .read_into(index, &mut bytes)
+            .ok_or(Error::OutOfBounds {
+                target: "DataView",
+                offset,
+                len: self.byte_len(),
+            })?;
+        Ok(bytes)
+    }
+
+    /// Writes `bytes` at `offset`, failing if they are not all inside the
+    /// view.
+    pub fn set_bytes<const N: usize>(
+        &mut self,
+        offset: usize,
+        bytes: [u8; N],
+    ) -> Result<(), Error> {
+        let index = self.byte_index(offset, N)?;
+        self._buffer.check_writable()?;
+        self._buffer
+            .write_from(index, &bytes)
+            .ok_or(Error::OutOfBounds {
+                target: "DataView",
+                offset,
+                len: self.byte_len(),
+            })
+    }
+}
+
+impl std::fmt::Display for DataView {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            fmt,
+            "DataView {{ byteOffset: {}, byteLength: {} }}",
+            self._offset,
+            self.byte_len()
+        )
+    }
+}
+
+macro_rules! add_accessors {
+    ($methods:ident, $type:ty, $variant:ident, $from_numeric:expr) => {
+        let kind = <$type as TypedArrayElement>::kind();
+        $methods.add_method(
+            &format!("get{}", kind),
+            move |_, this, args: (usize, Option<bool>)| -> Result<TypedArrayVariant, _> {
+                let (offset, little_endian) = args;
+                let bytes = this.get_bytes(offset)?;
+                Ok(TypedArrayVariant::$variant(
+                    if little_endian.unwrap_or(false) {
+                        <$type>::from_le_bytes(bytes)
+                    } else {
+                        <$type>::from_be_bytes(bytes)
+                    },
+                ))
+            },
+        );
+        $methods.add_method_mut(
+            &format!("set{}", kind),
+            move |_, this, args: (usize, Numeric, Option<bool>)| -> Result<(), _> {
+                let (offset, value, little_endian) = args;
+                let value: $type = $from_numeric(this, value)?;
+                let bytes = if little_endian.unwrap_or(false) {
+                    value.to_le_bytes()
+                } else {
+                    value.to_be_bytes()
+                };
+                Ok(this.set_bytes(offset, bytes)?)
+            },
+        );
+    };
+    ($methods:ident, integer $type:ty, $variant:ident) => {
+        // Integers never go through f64, so that 64-bit values round-trip
+        // exactly.
+        add_accessors!(
+            $methods,
+            $type,
+            $variant,
+            |this: &DataView, value: Numeric| {
+                value
+                    .to_integer(<$type>::MIN.into(), <$type>::MAX.into(), this._overflow)
+                    .map(|wide| wide as $type)
+                    .ok_or(Error::Overflow { target: "DataView" })
+            }
+        );
+    };
+    ($methods:ident, float $type:ty, $variant:ident) => {
+        add_accessors!($methods, $type, $variant, |_: &DataView, value: Numeric| {
+            Ok::<_, Error>(value.to_number() as $type)
+        });
+    };
+}
+
+impl mlua::UserData for DataView {
+    fn add_fields<'lua, F: mlua::UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("buffer", |_, this| Ok(this.buffer()));
+        fields.add_field_method_get("byteLength", |_, this| Ok(this.byte_len()));
+        fields.add_field_method_get("byteOffset", |_, this| Ok(this.byte_offset()));
+        fields.add_field_method_get("overflow", |_, this| Ok(this._overflow.name()));
+        fields.add_field_method_set("overflow", |_, this, name: String| {
+            this._overflow = OverflowPolicy::parse(&name)?;
+            Ok(())
+        });
+    }
+
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_function(
+            mlua::MetaMethod::ToString,
+            |_, this: DataView| -> Result<String, _> { Ok(format!("{this}")) },
+        );
+
+        add_accessors!(methods, integer i8, SInt8);
+        add_accessors!(methods, integer u8, UInt8);
+
+        add_accessors!(methods, integer i16, SInt16);
+        add_accessors!(methods, integer u16, UInt16);
+
+        add_accessors!(methods, integer i32, SInt32);
+        add_accessors!(methods, integer u32, UInt32);
+
+        add_accessors!(methods, integer i64, SInt64);
+        add_accessors!(methods, integer u64, UInt64);
+
+        add_accessors!(methods, float f32, Float32);
+        add_accessors!(methods, float f64, Float64);
+    }
+}
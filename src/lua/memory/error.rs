@@ -0,0 +1,385 @@
+use super::{ArrayBuffer, DataView, SharedArrayBuffer, TypedArray};
+
+/// Classification of an [`Error`], named after the JavaScript
+/// error it corresponds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// An argument has the right type but an unusable value, such as a
+    /// negative length or a misaligned offset.
+    Range,
+    /// An argument has a type that no overload accepts.
+    Type,
+    /// A string argument does not follow the format it is parsed as.
+    Syntax,
+    /// The operating system failed to open or map a file.
+    Io,
+}
+
+impl ErrorKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Range => "RangeError",
+            ErrorKind::Type => "TypeError",
+            ErrorKind::Syntax => "SyntaxError",
+            ErrorKind::Io => "IOError",
+        }
+    }
+}
+
+/// Everything that can go wrong in the memory module.
+///
+/// In Lua it is the error object raised by the constructors, exposing
+/// `kind`, `reason` (the variant name), `message` and the 1-based `argument`
+/// position at fault, if any. Methods are Rust callbacks, whose errors mlua
+/// always raises wrapped; `memory.toError` unwraps them into the same object.
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// An element index or byte offset past the end of `target`.
+    OutOfBounds {
+        target: &'static str,
+        offset: usize,
+        len: usize,
+    },
+    /// An offset or length that is not a multiple of the element size, with
+    /// the position of the argument it comes from.
+    Misaligned {
+        target: &'static str,
+        what: &'static str,
+        value: usize,
+        alignment: usize,
+        argument: Option<usize>,
+    },
+    /// A typed array of a kind the operation does not support.
+    KindMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// A value that does not fit the element type of `target` under its
+    /// overflow policy.
+    Overflow { target: &'static str },
+    /// A length above the `maxByteLength` of a resizable buffer.
+    ExceedsMaxLength { len: usize, max_len: usize },
+    /// Resizing a fixed-length buffer.
+    NotResizable,
+    /// Accessing the contents of a detached buffer.
+    Detached,
+    /// Failing to allocate the memory of a buffer.
+    Allocation,
+    /// Flushing a buffer that is not mapped from a file with writes enabled.
+    NotFlushable,
+    /// Writing to a buffer mapped from a file without writes enabled.
+    ReadOnly,
+    /// A failed file operation, with the description of the OS error.
+    Io {
+        operation: &'static str,
+        message: String,
+    },
+    /// Malformed base64 or hexadecimal input.
+    Syntax { format: &'static str, offset: usize },
+    /// Arguments matching none of the overloads of a function, or options
+    /// with unknown values.
+    Argument {
+        kind: ErrorKind,
+        argument: Option<usize>,
+        message: String,
+    },
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::OutOfBounds { .. }
+            | Error::Misaligned { .. }
+            | Error::Overflow { .. }
+            | Error::ExceedsMaxLength { .. }
+            | Error::Allocation => ErrorKind::Range,
+            Error::KindMismatch { .. }
+            | Error::NotResizable
+            | Error::Detached
+            | Error::NotFlushable
+            | Error::ReadOnly => ErrorKind::Type,
+            Error::Io { .. } => ErrorKind::Io,
+            Error::Syntax { .. } => ErrorKind::Syntax,
+            Error::Argument { kind, .. } => *kind,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Error::OutOfBounds { .. } => "OutOfBounds",
+            Error::Misaligned { .. } => "Misaligned",
+            Error::KindMismatch { .. } => "KindMismatch",
+            Error::Overflow { .. } => "Overflow",
+            Error::ExceedsMaxLength { .. } => "ExceedsMaxLength",
+            Error::NotResizable => "NotResizable",
+            Error::Detached => "Detached",
+            Error::Allocation => "Allocation",
+            Error::NotFlushable => "NotFlushable",
+            Error::ReadOnly => "ReadOnly",
+            Error::Io { .. } => "Io",
+            Error::Syntax { .. } => "Syntax",
+            Error::Argument { .. } => "Argument",
+        }
+    }
+
+    /// The 1-based position of the offending argument of a constructor.
+    pub fn argument(&self) -> Option<usize> {
+        match self {
+            Error::Argument { argument, .. } | Error::Misaligned { argument, .. } => *argument,
+            _ => None,
+        }
+    }
+
+    /// The description of the error, without its kind.
+    pub fn message(&self) -> String {
+        match self {
+            Error::OutOfBounds {
+                target,
+                offset,
+                len,
+            } => format!(
+                "offset {} is out of bounds for {} of length {}",
+                offset, target, len
+            ),
+            Error::Misaligned {
+                target,
+                what,
+                value,
+                alignment,
+                ..
+            } => format!(
+                "{} {} of {} should be a multiple of {}",
+                what, value, target, alignment
+            ),
+            Error::KindMismatch { expected, found } => {
+                format!("expected {}, got {}", expected, found)
+            }
+            Error::Overflow { target } => format!("value out of range for {}", target),
+            Error::ExceedsMaxLength { len, max_len } => {
+                format!("length {} exceeds the maxByteLength {}", len, max_len)
+            }
+            Error::NotResizable => "cannot resize a fixed-length ArrayBuffer".into(),
+            Error::Detached => "attempting to access a detached ArrayBuffer".into(),
+            Error::Allocation => "failed to allocate memory for the array buffer".into(),
+            Error::NotFlushable => {
+                "only an ArrayBuffer mapped with mode 'rw' can be flushed".into()
+            }
+            Error::ReadOnly => "cannot write to an ArrayBuffer mapped with mode 'r'".into(),
+            Error::Io { operation, message } => format!("failed to {}: {}", operation, message),
+            Error::Syntax { format, offset } => {
+                format!("invalid {} string at offset {}", format, offset)
+            }
+            Error::Argument { message, .. } => message.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(fmt, "{}: {}", self.kind().name(), self.message())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Error> for mlua::Error {
+    fn from(err: Error) -> Self {
+        mlua::Error::external(err)
+    }
+}
+
+impl mlua::UserData for Error {
+    fn add_fields<'lua, F: mlua::UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("kind", |_, this| Ok(this.kind().name()));
+        fields.add_field_method_get("reason", |_, this| Ok(this.reason()));
+        fields.add_field_method_get("message", |_, this| Ok(this.message()));
+        fields.add_field_method_get("argument", |_, this| Ok(this.argument()));
+    }
+
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(mlua::MetaMethod::ToString, |_, this, ()| {
+            Ok(this.to_string())
+        });
+    }
+}
+
+/// Finds the memory [`Error`] behind an error raised from Rust, looking
+/// through the callback errors wrapping it.
+fn find(err: &mlua::Error) -> Option<&Error> {
+    match err {
+        mlua::Error::CallbackError { cause, .. } => find(cause),
+        mlua::Error::ExternalError(err) => err.downcast_ref::<Error>(),
+        _ => None,
+    }
+}
+
+/// Implements `memory.toError(err)`: returns the memory error object behind a
+/// value caught with `pcall`, or `nil` for any other error.
+pub(super) fn to_error<'lua>(
+    lua: &'lua mlua::Lua,
+    value: mlua::Value<'lua>,
+) -> mlua::Result<Option<mlua::AnyUserData<'lua>>> {
+    match value {
+        mlua::Value::UserData(ud) if ud.is::<Error>() => Ok(Some(ud)),
+        mlua::Value::Error(err) => find(&err)
+            .map(|err| lua.create_userdata(err.clone()))
+            .transpose(),
+        _ => Ok(None),
+    }
+}
+
+/// Describes how a value would be spelled in a signature, naming the memory
+/// userdata types instead of just "userdata".
+fn type_name(value: &mlua::Value) -> &'static str {
+    match value {
+        mlua::Value::UserData(ud) if ud.is::<ArrayBuffer>() => "ArrayBuffer",
+        mlua::Value::UserData(ud) if ud.is::<SharedArrayBuffer>() => "SharedArrayBuffer",
+        mlua::Value::UserData(ud) if ud.is::<TypedArray>() => "TypedArray",
+        mlua::Value::UserData(ud) if ud.is::<DataView>() => "DataView",
+        value => value.type_name(),
+    }
+}
+
+/// The name and accepted signatures of a constructor, used to word its
+/// errors.
+pub(super) struct Overloads<'a> {
+    pub name: &'a str,
+    /// Parameter lists, without the constructor name.
+    pub signatures: &'a [&'a str],
+}
+
+impl Overloads<'_> {
+    fn signatures(&self) -> String {
+        self.signatures
+            .iter()
+            .map(|signature| format!("\n\t{}{}", self.name, signature))
+            .collect()
+    }
+
+    /// An argument whose type no overload accepts at `position`.
+    pub fn type_error(&self, position: usize, expected: &str, got: &mlua::Value) -> mlua::Error {
+        mlua::Error::external(Error::Argument {
+            kind: ErrorKind::Type,
+            message: format!(
+                "bad argument #{} to '{}' ({} expected, got {}); accepted signatures:{}",
+                position,
+                self.name,
+                expected,
+                type_name(got),
+                self.signatures()
+            ),
+            argument: Some(position),
+        })
+    }
+
+    /// More arguments than the longest matching overload takes.
+    pub fn too_many(&self, position: usize, got: &mlua::Value) -> mlua::Error {
+        self.type_error(position, "no value", got)
+    }
+
+    /// An argument with an unusable value, or an invalid combination of
+    /// arguments if `position` is `None`.
+    pub fn range_error(&self, position: Option<usize>, message: String) -> mlua::Error {
+        mlua::Error::external(Error::Argument {
+            kind: ErrorKind::Range,
+            message: match position {
+                Some(position) => {
+                    format!(
+                        "bad argument #{} to '{}' ({})",
+                        position, self.name, message
+                    )
+                }
+                None => message,
+            },
+            argument: position,
+        })
+    }
+
+    /// Reads an optional non-negative integer such as a length or an offset,
+    /// passed as argument `position`; `nil` and missing values give `None`.
+    pub fn index(
+        &self,
+        value: Option<&mlua::Value>,
+        position: usize,
+        what: &str,
+    ) -> mlua::Result<Option<usize>> {
+        match value {
+            None | Some(mlua::Value::Nil) => Ok(None),
+            Some(mlua::Value::Integer(v)) => usize::try_from(*v)
+                .map(Some)
+                .map_err(|_| self.range_error(Some(position), format!("invalid {} {}", what, v))),
+            Some(mlua::Value::Number(v)) if v.fract() == 0.0 && *v >= 0.0 => {
+                usize::try_from(*v as u64).map(Some).map_err(|_| {
+                    self.range_error(Some(position), format!("invalid {} {}", what, v))
+                })
+            }
+            Some(mlua::Value::Number(v)) => {
+                Err(self.range_error(Some(position), format!("invalid {} {}", what, v)))
+            }
+            Some(value) => Err(self.type_error(position, "number", value)),
+        }
+    }
+
+    /// Fails if more than `max` arguments were passed.
+    pub fn check_count(&self, args: &[mlua::Value], max: usize) -> mlua::Result<()> {
+        match args.get(max) {
+            Some(extra) => Err(self.too_many(max + 1, extra)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Converts the result of a constructor into the `(ok, value)` pair expected
+/// by the functions built with [`raising`].
+pub(super) fn outcome<'lua, T: mlua::ToLua<'lua>>(
+    lua: &'lua mlua::Lua,
+    result: mlua::Result<T>,
+) -> mlua::Result<(bool, mlua::Value<'lua>)> {
+    match result {
+        Ok(value) => Ok((true, value.to_lua(lua)?)),
+        Err(mlua::Error::ExternalError(err)) => match err.downcast_ref::<Error>() {
+            Some(err) => Ok((
+                false,
+                mlua::Value::UserData(lua.create_userdata(err.clone())?),
+            )),
+            None => Err(mlua::Error::ExternalError(err)),
+        },
+        Err(err) => Err(err),
+    }
+}
+
+/// Name of the registry value caching the Lua function behind [`raising`].
+const RAISING: &str = "memory.raising";
+
+/// Wraps `function`, which returns an `(ok, value)` pair, into a function
+/// that returns `value` or raises it as the error object.
+///
+/// Errors returned from Rust callbacks reach Lua as opaque wrappers, so
+/// raising an [`Error`] scripts can inspect takes a Lua frame. The wrapper
+/// is compiled once per state and shared by every constructor.
+pub(super) fn raising<'lua>(
+    lua: &'lua mlua::Lua,
+    function: mlua::Function<'lua>,
+) -> mlua::Result<mlua::Function<'lua>> {
+    let wrap = match lua.named_registry_value::<_, Option<mlua::Function>>(RAISING)? {
+        Some(wrap) => wrap,
+        None => {
+            let wrap = lua
+                .load(
+                    "local construct = ...
+                    return function(...)
+                        local ok, value = construct(...)
+                        if ok then
+                            return value
+                        end
+                        error(value, 2)
+                    end",
+                )
+                .set_name("=memory")?
+                .into_function()?;
+            lua.set_named_registry_value(RAISING, wrap.clone())?;
+            wrap
+        }
+    };
+    wrap.call(function)
+}
@@ -0,0 +1,248 @@
+/// Lua's floor division and modulo, which differ from Rust's truncating
+/// operators for signed operands.
+trait FloorDivision: Sized {
+    fn floor_div(self, rhs: Self) -> Self;
+
+    fn floor_mod(self, rhs: Self) -> Self;
+}
+
+impl FloorDivision for i128 {
+    fn floor_div(self, rhs: Self) -> Self {
+        let quotient = self.wrapping_div(rhs);
+        if self.wrapping_rem(rhs) != 0 && (self < 0) != (rhs < 0) {
+            quotient - 1
+        } else {
+            quotient
+        }
+    }
+
+    fn floor_mod(self, rhs: Self) -> Self {
+        let remainder = self.wrapping_rem(rhs);
+        if remainder != 0 && (remainder < 0) != (rhs < 0) {
+            remainder + rhs
+        } else {
+            remainder
+        }
+    }
+}
+
+impl FloorDivision for u128 {
+    fn floor_div(self, rhs: Self) -> Self {
+        self / rhs
+    }
+
+    fn floor_mod(self, rhs: Self) -> Self {
+        self % rhs
+    }
+}
+
+/// Parses an optionally signed decimal or `0x` prefixed hexadecimal string,
+/// wrapping around like Lua integer literals do.
+fn parse(text: &str) -> Option<u128> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let bits = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u128::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<u128>().ok()?,
+    };
+    Some(if negative { bits.wrapping_neg() } else { bits })
+}
+
+/// Reads the two's complement bits of a 128-bit operand.
+///
+/// Accepts `Int128`/`UInt128` values, integers (sign-extended), floats with an
+/// exact integer representation and strings understood by [`parse`].
+pub(super) fn to_bits(value: &mlua::Value) -> mlua::Result<u128> {
+    match value {
+        mlua::Value::Integer(v) => Ok(*v as i128 as u128),
+        mlua::Value::Number(v)
+            if v.fract() == 0.0 && (-2f64.powi(127)..2f64.powi(127)).contains(v) =>
+        {
+            Ok(*v as i128 as u128)
+        }
+        mlua::Value::Number(v) if v.fract() == 0.0 && (0.0..2f64.powi(128)).contains(v) => {
+            Ok(*v as u128)
+        }
+        mlua::Value::Number(_) => Err(mlua::Error::RuntimeError(
+            "number has no integer representation".into(),
+        )),
+        mlua::Value::String(text) => {
+            text.to_str()
+                .ok()
+                .and_then(parse)
+                .ok_or_else(|| mlua::Error::FromLuaConversionError {
+                    from: "string",
+                    to: "128-bit integer",
+                    message: Some("expected a decimal or hexadecimal integer".into()),
+                })
+        }
+        mlua::Value::UserData(ud) => {
+            if let Ok(v) = ud.borrow::<Int128>() {
+                Ok(v.0 as u128)
+            } else if let Ok(v) = ud.borrow::<UInt128>() {
+                Ok(v.0)
+            } else {
+                Err(mlua::Error::FromLuaConversionError {
+                    from: "userdata",
+                    to: "128-bit integer",
+                    message: None,
+                })
+            }
+        }
+        value => Err(mlua::Error::FromLuaConversionError {
+            from: value.type_name(),
+            to: "128-bit integer",
+            message: None,
+        }),
+    }
+}
+
+/// Shifts left by `shift` bits, or right for negative shifts, as Lua's `<<`.
+fn shift_left(bits: u128, shift: i128) -> u128 {
+    match shift {
+        0..=127 => bits << shift,
+        -127..=-1 => bits >> -shift,
+        _ => 0,
+    }
+}
+
+macro_rules! int128_type {
+    ($name:ident, $type:ty) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+        pub struct $name(pub $type);
+
+        impl $name {
+            pub fn hi(self) -> mlua::Integer {
+                ((self.0 as u128) >> 64) as u64 as mlua::Integer
+            }
+
+            pub fn lo(self) -> mlua::Integer {
+                self.0 as u128 as u64 as mlua::Integer
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(fmt, "{}", self.0)
+            }
+        }
+
+        impl mlua::UserData for $name {
+            fn add_fields<'lua, F: mlua::UserDataFields<'lua, Self>>(fields: &mut F) {
+                fields.add_field_method_get("hi", |_, this| Ok(this.hi()));
+                fields.add_field_method_get("lo", |_, this| Ok(this.lo()));
+            }
+
+            fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+                methods.add_method("tonumber", |_, this, ()| Ok(this.0 as mlua::Number));
+                methods.add_meta_method(mlua::MetaMethod::ToString, |_, this, ()| {
+                    Ok(this.to_string())
+                });
+
+                // Both operands are converted to this type, so mixing the two
+                // 128-bit types reinterprets the right operand's bits.
+                macro_rules! add_operator {
+                    ($method:ident, $op:expr) => {
+                        methods.add_meta_function(
+                            mlua::MetaMethod::$method,
+                            |_, args: (mlua::Value, mlua::Value)| -> mlua::Result<$name> {
+                                let a = to_bits(&args.0)? as $type;
+                                let b = to_bits(&args.1)? as $type;
+                                let op: fn($type, $type) -> mlua::Result<$type> = $op;
+                                op(a, b).map($name)
+                            },
+                        );
+                    };
+                }
+                macro_rules! add_comparison {
+                    ($method:ident, $op:expr) => {
+                        methods.add_meta_function(
+                            mlua::MetaMethod::$method,
+                            |_, args: (mlua::Value, mlua::Value)| -> mlua::Result<bool> {
+                                let a = to_bits(&args.0)? as $type;
+                                let b = to_bits(&args.1)? as $type;
+                                let op: fn($type, $type) -> bool = $op;
+                                Ok(op(a, b))
+                            },
+                        );
+                    };
+                }
+
+                add_operator!(Add, |a, b| Ok(a.wrapping_add(b)));
+                add_operator!(Sub, |a, b| Ok(a.wrapping_sub(b)));
+                add_operator!(Mul, |a, b| Ok(a.wrapping_mul(b)));
+                add_operator!(IDiv, |a, b| match b {
+                    0 => Err(mlua::Error::RuntimeError(
+                        "attempt to perform 'n//0'".into()
+                    )),
+                    b => Ok(a.floor_div(b)),
+                });
+                add_operator!(Mod, |a, b| match b {
+                    0 => Err(mlua::Error::RuntimeError("attempt to perform 'n%0'".into())),
+                    b => Ok(a.floor_mod(b)),
+                });
+                add_operator!(Unm, |a, _| Ok(a.wrapping_neg()));
+                add_operator!(BAnd, |a, b| Ok(a & b));
+                add_operator!(BOr, |a, b| Ok(a | b));
+                add_operator!(BXor, |a, b| Ok(a ^ b));
+                add_operator!(BNot, |a, _| Ok(!a));
+                add_operator!(Shl, |a, b| Ok(shift_left(a as u128, b as i128) as $type));
+                add_operator!(Shr, |a, b| Ok(
+                    shift_left(a as u128, (b as i128).saturating_neg()) as $type
+                ));
+
+                methods.add_meta_function(
+                    mlua::MetaMethod::Div,
+                    |_, args: (mlua::Value, mlua::Value)| -> mlua::Result<mlua::Number> {
+                        let a = to_bits(&args.0)? as $type;
+                        let b = to_bits(&args.1)? as $type;
+                        Ok(a as mlua::Number / b as mlua::Number)
+                    },
+                );
+
+                add_comparison!(Eq, |a, b| a == b);
+                add_comparison!(Lt, |a, b| a < b);
+                add_comparison!(Le, |a, b| a <= b);
+            }
+        }
+    };
+}
+
+int128_type!(Int128, i128);
+int128_type!(UInt128, u128);
+
+/// Implements `memory.Int128(value)`/`memory.UInt128(value)` and their
+/// `(hi, lo)` form.
+pub(super) fn construct<T: From<u128>>(
+    args: (mlua::Value, Option<mlua::Integer>),
+) -> mlua::Result<T> {
+    match args {
+        (mlua::Value::Integer(hi), Some(lo)) => {
+            Ok(T::from(((hi as u64 as u128) << 64) | lo as u64 as u128))
+        }
+        (value, None) => to_bits(&value).map(T::from),
+        (value, Some(_)) => Err(mlua::Error::FromLuaConversionError {
+            from: value.type_name(),
+            to: "integer",
+            message: Some("the high half must be an integer".into()),
+        }),
+    }
+}
+
+impl From<u128> for Int128 {
+    fn from(bits: u128) -> Self {
+        Int128(bits as i128)
+    }
+}
+
+impl From<u128> for UInt128 {
+    fn from(bits: u128) -> Self {
+        UInt128(bits)
+    }
+}
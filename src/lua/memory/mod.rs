@@ -1,8 +1,75 @@
 mod array_buffer;
+mod array_buffer_like;
+mod atomics;
+mod base64;
+mod data_view;
+mod error;
+mod int128;
+mod pack;
+mod shared_array_buffer;
+mod struct_view;
 mod typed_array;
+mod typed_array_ref;
 
 pub use array_buffer::ArrayBuffer;
-pub use typed_array::TypedArray;
+pub use array_buffer_like::ArrayBufferLike;
+pub use data_view::DataView;
+pub use error::{Error, ErrorKind};
+pub use int128::{Int128, UInt128};
+pub use pack::PackFormat;
+pub use shared_array_buffer::SharedArrayBuffer;
+pub use struct_view::{StructArray, StructField, StructLayout, StructRef};
+pub use typed_array::{ClampedU8, OverflowPolicy, TypedArray, TypedArrayElement, TypedArrayKind};
+pub use typed_array_ref::{Iter, TypedArrayRef, TypedArrayRefMut};
+
+use error::{outcome, raising, to_error, Overloads};
+
+/// Resolves 1-based inclusive `begin`/`end` bounds into a 0-based range over
+/// `len` items, following `string.sub`: negative values count from the end,
+/// `begin` defaults to 1 and `end` to -1.
+fn relative_range(
+    len: usize,
+    begin: Option<mlua::Integer>,
+    end: Option<mlua::Integer>,
+) -> std::ops::Range<usize> {
+    let resolve = |index: mlua::Integer| -> usize {
+        if index < 0 {
+            len.saturating_sub(index.unsigned_abs() as usize - 1)
+        } else {
+            (index as u64).min(len as u64) as usize
+        }
+    };
+    let start = match begin.unwrap_or(1) {
+        begin if begin > 0 => ((begin - 1) as u64).min(len as u64) as usize,
+        begin => resolve(begin).max(1) - 1,
+    };
+    let end = resolve(end.unwrap_or(-1));
+    start..end.max(start)
+}
+
+/// Resolves an optional 0-based byte `offset` and `length` within the `len`
+/// bytes of `target`, covering the rest of it by default.
+fn byte_range(
+    target: &'static str,
+    len: usize,
+    offset: Option<usize>,
+    length: Option<usize>,
+) -> Result<std::ops::Range<usize>, Error> {
+    let offset = offset.unwrap_or(0);
+    let available = len.checked_sub(offset).ok_or(Error::OutOfBounds {
+        target,
+        offset,
+        len,
+    })?;
+    match length {
+        Some(length) if length > available => Err(Error::OutOfBounds {
+            target,
+            offset: offset.saturating_add(length),
+            len,
+        }),
+        length => Ok(offset..offset + length.unwrap_or(available)),
+    }
+}
 
 enum TypedArrayConstructor {
     Default,
@@ -10,104 +77,372 @@ enum TypedArrayConstructor {
         length: usize,
     },
     WithBuffer {
-        buffer: ArrayBuffer,
+        buffer: ArrayBufferLike,
     },
     WithOffset {
-        buffer: ArrayBuffer,
+        buffer: ArrayBufferLike,
         offset: usize,
     },
     New {
-        buffer: ArrayBuffer,
+        buffer: ArrayBufferLike,
         offset: usize,
         length: usize,
     },
+    WithArray {
+        source: TypedArray,
+    },
+    WithValues {
+        values: Vec<typed_array::Numeric>,
+    },
+}
+
+/// Collects the values produced by a generic `for` iterator triplet.
+///
+/// The second result of each step is taken when there is one, so both
+/// `array:values()` and `ipairs(t)` style iterators yield their values.
+fn collect_iterator<'lua>(
+    function: mlua::Function<'lua>,
+    state: mlua::Value<'lua>,
+    mut control: mlua::Value<'lua>,
+) -> mlua::Result<Vec<mlua::Value<'lua>>> {
+    let mut values = Vec::new();
+    loop {
+        let mut results = function
+            .call::<_, mlua::MultiValue>((state.clone(), control))?
+            .into_iter();
+        control = results.next().unwrap_or(mlua::Value::Nil);
+        if let mlua::Value::Nil = control {
+            return Ok(values);
+        }
+        values.push(results.next().unwrap_or_else(|| control.clone()));
+    }
+}
+
+/// Lists the elements of a sequence table, a string (its bytes), a typed
+/// array or an iterator function.
+fn iterable_values<'lua>(
+    lua: &'lua mlua::Lua,
+    iterable: mlua::Value<'lua>,
+) -> mlua::Result<Vec<mlua::Value<'lua>>> {
+    match iterable {
+        mlua::Value::Table(table) => table.sequence_values().collect(),
+        mlua::Value::String(string) => Ok(string
+            .as_bytes()
+            .iter()
+            .map(|byte| mlua::Value::Integer((*byte).into()))
+            .collect()),
+        mlua::Value::Function(function) => {
+            collect_iterator(function, mlua::Value::Nil, mlua::Value::Nil)
+        }
+        mlua::Value::UserData(ud) if ud.is::<TypedArray>() => ud
+            .borrow::<TypedArray>()?
+            .iter()
+            .map(|value| mlua::ToLua::to_lua(value, lua))
+            .collect(),
+        value => Err(mlua::Error::FromLuaConversionError {
+            from: value.type_name(),
+            to: "iterable",
+            message: Some("expected a table, a string, a typed array or a function".into()),
+        }),
+    }
+}
+
+fn to_numerics<'lua>(
+    lua: &'lua mlua::Lua,
+    values: Vec<mlua::Value<'lua>>,
+) -> mlua::Result<Vec<typed_array::Numeric>> {
+    values
+        .into_iter()
+        .map(|value| mlua::FromLua::from_lua(value, lua))
+        .collect()
 }
 
-impl<'lua> mlua::FromLuaMulti<'lua> for TypedArrayConstructor {
-    fn from_lua_multi(values: mlua::MultiValue<'lua>, lua: &'lua mlua::Lua) -> mlua::Result<Self> {
-        match values.len() {
-            0 => Ok(TypedArrayConstructor::Default),
-            1 => {
-                let first = &values[0];
-                <usize as mlua::FromLua>::from_lua(first.clone(), &lua)
-                    .map(|length| TypedArrayConstructor::WithLength { length })
-                    .or_else(|_| {
-                        <ArrayBuffer as mlua::FromLua>::from_lua(first.clone(), &lua)
-                            .map(|buffer| TypedArrayConstructor::WithBuffer { buffer })
+const TYPED_ARRAY_SIGNATURES: &[&str] = &[
+    "()",
+    "(length)",
+    "(table | string | typedArray)",
+    "(iterator [, state [, control]])",
+    "(buffer [, byteOffset [, length]])",
+];
+
+impl TypedArrayConstructor {
+    fn parse<'lua>(
+        lua: &'lua mlua::Lua,
+        overloads: &Overloads,
+        args: Vec<mlua::Value<'lua>>,
+    ) -> mlua::Result<Self> {
+        let values = |values: Vec<mlua::Value<'lua>>| {
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    mlua::FromLua::from_lua(value.clone(), lua).map_err(|_| {
+                        overloads.type_error(1, &format!("number at index {}", i + 1), &value)
                     })
+                })
+                .collect::<mlua::Result<Vec<_>>>()
+        };
+        let first = match args.first() {
+            None => return Ok(TypedArrayConstructor::Default),
+            Some(first) => first,
+        };
+        match first {
+            mlua::Value::Function(function) => {
+                overloads.check_count(&args, 3)?;
+                let state = args.get(1).cloned().unwrap_or(mlua::Value::Nil);
+                let control = args.get(2).cloned().unwrap_or(mlua::Value::Nil);
+                Ok(TypedArrayConstructor::WithValues {
+                    values: values(collect_iterator(function.clone(), state, control)?)?,
+                })
             }
-            2 => <(ArrayBuffer, usize) as mlua::FromLuaMulti>::from_lua_multi(values, &lua)
-                .map(|(buffer, offset)| TypedArrayConstructor::WithOffset { buffer, offset }),
-            3 => <(ArrayBuffer, usize, usize) as mlua::FromLuaMulti>::from_lua_multi(values, &lua)
-                .map(|(buffer, offset, length)| TypedArrayConstructor::New {
-                    buffer,
-                    offset,
-                    length,
-                }),
-            _ => Err(mlua::Error::RuntimeError(format!(
-                "couldn't find suitable overload from {values:?}"
-            ))),
+            // A string is the sequence of its bytes, even when it looks like
+            // a number: `UInt8Array("3")` holds the byte 51 rather than three
+            // zeros.
+            mlua::Value::Table(_) | mlua::Value::String(_) => {
+                overloads.check_count(&args, 1)?;
+                Ok(TypedArrayConstructor::WithValues {
+                    values: values(iterable_values(lua, first.clone())?)?,
+                })
+            }
+            mlua::Value::UserData(ud) if ud.is::<TypedArray>() => {
+                overloads.check_count(&args, 1)?;
+                Ok(TypedArrayConstructor::WithArray {
+                    source: ud.borrow::<TypedArray>()?.clone(),
+                })
+            }
+            mlua::Value::Integer(_) | mlua::Value::Number(_) => {
+                overloads.check_count(&args, 1)?;
+                let length = overloads.index(Some(first), 1, "length")?.unwrap_or(0);
+                Ok(TypedArrayConstructor::WithLength { length })
+            }
+            mlua::Value::UserData(_) => {
+                let buffer = <ArrayBufferLike as mlua::FromLua>::from_lua(first.clone(), lua)
+                    .map_err(|_| {
+                        overloads.type_error(1, "ArrayBuffer or SharedArrayBuffer", first)
+                    })?;
+                overloads.check_count(&args, 3)?;
+                let offset = overloads.index(args.get(1), 2, "byteOffset")?;
+                let length = overloads.index(args.get(2), 3, "length")?;
+                Ok(match (offset, length) {
+                    (None, None) => TypedArrayConstructor::WithBuffer { buffer },
+                    (Some(offset), None) => TypedArrayConstructor::WithOffset { buffer, offset },
+                    (offset, Some(length)) => TypedArrayConstructor::New {
+                        buffer,
+                        offset: offset.unwrap_or(0),
+                        length,
+                    },
+                })
+            }
+            value => Err(overloads.type_error(
+                1,
+                "number, table, string, function, TypedArray or ArrayBuffer",
+                value,
+            )),
         }
     }
 }
 
+/// Registers `XxxArray` as a callable table holding the static `from` and
+/// `of` constructors; `type(memory.Int8Array)` is therefore `"table"`, no
+/// longer `"function"`.
 macro_rules! add_typed_array {
     ($lua:ident, $table:ident, $type:ty) => {
         let kind = <$type as typed_array::TypedArrayElement>::kind();
-        $table.raw_set(
-            format!("{}Array", kind),
+        let constructor = $lua.create_table()?;
+        constructor.raw_set("BYTES_PER_ELEMENT", kind.bytes_per_element())?;
+        constructor.raw_set(
+            "from",
             $lua.create_function(
-                move |_, args: TypedArrayConstructor| -> Result<TypedArray, _> {
-                    match args {
+                move |lua, args: (mlua::Value, Option<mlua::Function>)| -> Result<TypedArray, _> {
+                    let (iterable, map) = args;
+                    match (iterable, map) {
+                        (mlua::Value::UserData(ud), None) if ud.is::<TypedArray>() => {
+                            let source = ud.borrow::<TypedArray>()?.clone();
+                            TypedArray::from_array(kind, &source)
+                        }
+                        (iterable, None) => TypedArray::from_values(
+                            kind,
+                            &to_numerics(lua, iterable_values(lua, iterable)?)?,
+                        ),
+                        (iterable, Some(map)) => {
+                            let values = iterable_values(lua, iterable)?
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, value)| map.call((value, i + 1)))
+                                .collect::<mlua::Result<Vec<_>>>()?;
+                            TypedArray::from_values(kind, &values)
+                        }
+                    }
+                },
+            )?,
+        )?;
+        constructor.raw_set(
+            "of",
+            $lua.create_function(
+                move |_, values: mlua::Variadic<typed_array::Numeric>| -> Result<TypedArray, _> {
+                    TypedArray::from_values(kind, &values)
+                },
+            )?,
+        )?;
+        if kind == typed_array::TypedArrayKind::UInt8 {
+            constructor.raw_set(
+                "fromBase64",
+                $lua.create_function(|_, args: (mlua::String, Option<mlua::Table>)| {
+                    let options = base64::Options::from_table(args.1)?;
+                    Ok(base64::decode(args.0.as_bytes(), options, usize::MAX).into_array()?)
+                })?,
+            )?;
+            constructor.raw_set(
+                "fromHex",
+                $lua.create_function(|_, s: mlua::String| {
+                    Ok(base64::decode_hex(s.as_bytes(), usize::MAX).into_array()?)
+                })?,
+            )?;
+        }
+        let name = format!("{}Array", kind);
+        let call =
+            $lua.create_function(move |lua, args: (mlua::Table, mlua::MultiValue)| {
+                let overloads = Overloads {
+                    name: &name,
+                    signatures: TYPED_ARRAY_SIGNATURES,
+                };
+                let result = TypedArrayConstructor::parse(lua, &overloads, args.1.into_vec())
+                    .and_then(|args| match args {
                         TypedArrayConstructor::Default => {
-                            TypedArray::with_buffer(kind, array_buffer::ArrayBuffer::default())
-                                .map_err(|err| mlua::Error::RuntimeError(err.message()))
+                            Ok(TypedArray::with_buffer(kind, ArrayBufferLike::default())?)
                         }
                         TypedArrayConstructor::WithLength { length } => {
-                            TypedArray::with_len(kind, length)
-                                .map_err(|err| mlua::Error::MemoryError(format!("{err:?}")))
+                            Ok(TypedArray::with_len(kind, length)?)
                         }
                         TypedArrayConstructor::WithBuffer { buffer } => {
-                            TypedArray::with_buffer(kind, buffer)
-                                .map_err(|err| mlua::Error::RuntimeError(err.message()))
+                            Ok(TypedArray::with_buffer(kind, buffer)?)
                         }
                         TypedArrayConstructor::WithOffset { buffer, offset } => {
-                            TypedArray::with_offset(kind, buffer, offset)
-                                .map_err(|err| mlua::Error::RuntimeError(err.message()))
+                            Ok(TypedArray::with_offset(kind, buffer, offset)?)
                         }
                         TypedArrayConstructor::New {
                             buffer,
                             offset,
                             length,
-                        } => TypedArray::new(kind, buffer, offset, length)
-                            .map_err(|err| mlua::Error::RuntimeError(err.message())),
-                    }
-                },
-            )?,
-        )?;
+                        } => Ok(TypedArray::new(kind, buffer, offset, length)?),
+                        TypedArrayConstructor::WithArray { source } => {
+                            TypedArray::from_array(kind, &source)
+                        }
+                        TypedArrayConstructor::WithValues { values } => {
+                            TypedArray::from_values(kind, &values)
+                        }
+                    });
+                outcome(lua, result)
+            })?;
+        let metatable = $lua.create_table()?;
+        metatable.raw_set("__call", raising($lua, call)?)?;
+        constructor.set_metatable(Some(metatable));
+        $table.raw_set(format!("{}Array", kind), constructor)?;
     };
 }
 
 pub fn create_table<'lua>(lua: &'lua mlua::Lua) -> mlua::Result<mlua::Table<'lua>> {
     let memory_table = lua.create_table()?;
 
-    memory_table.raw_set(
-        "ArrayBuffer",
-        lua.create_function(|_, len: Option<usize>| -> Result<ArrayBuffer, _> {
-            if let Some(len) = len {
-                ArrayBuffer::new(len).map_err(|_| {
-                    mlua::Error::MemoryError(
-                        "Failed to allocate memory for the array buffer".into(),
-                    )
-                })
+    let array_buffer = lua.create_table()?;
+    array_buffer.raw_set(
+        "fromString",
+        lua.create_function(|_, s: mlua::String| Ok(ArrayBuffer::from_bytes(s.as_bytes())?))?,
+    )?;
+    let call = lua.create_function(|lua, args: (mlua::Table, mlua::MultiValue)| {
+        let overloads = Overloads {
+            name: "ArrayBuffer",
+            signatures: &["([byteLength [, { maxByteLength = n }]])"],
+        };
+        let args = args.1.into_vec();
+        let result = (|| {
+            overloads.check_count(&args, 2)?;
+            let len = overloads.index(args.first(), 1, "byteLength")?;
+            let max_len = match args.get(1) {
+                None | Some(mlua::Value::Nil) => None,
+                Some(mlua::Value::Table(options)) => overloads.index(
+                    Some(&options.get::<_, mlua::Value>("maxByteLength")?),
+                    2,
+                    "maxByteLength",
+                )?,
+                Some(value) => return Err(overloads.type_error(2, "table", value)),
+            };
+            match (len, max_len) {
+                (len, Some(max_len)) => Ok(ArrayBuffer::with_max_len(len.unwrap_or(0), max_len)?),
+                (Some(len), None) => Ok(ArrayBuffer::new(len)?),
+                (None, None) => Ok(ArrayBuffer::default()),
+            }
+        })();
+        outcome(lua, result)
+    })?;
+    let metatable = lua.create_table()?;
+    metatable.raw_set("__call", raising(lua, call)?)?;
+    array_buffer.set_metatable(Some(metatable));
+    memory_table.raw_set("ArrayBuffer", array_buffer)?;
+
+    let shared_array_buffer = lua.create_function(|lua, args: mlua::MultiValue| {
+        let overloads = Overloads {
+            name: "SharedArrayBuffer",
+            signatures: &["([byteLength])"],
+        };
+        let args = args.into_vec();
+        let result = (|| {
+            overloads.check_count(&args, 1)?;
+            if let Some(len) = overloads.index(args.first(), 1, "byteLength")? {
+                Ok(SharedArrayBuffer::new(len)?)
             } else {
-                Ok(ArrayBuffer::default())
+                Ok(SharedArrayBuffer::default())
             }
-        })?,
+        })();
+        outcome(lua, result)
+    })?;
+    memory_table.raw_set("SharedArrayBuffer", raising(lua, shared_array_buffer)?)?;
+
+    let data_view = lua.create_function(|lua, args: mlua::MultiValue| {
+        let overloads = Overloads {
+            name: "DataView",
+            signatures: &["(buffer [, byteOffset [, byteLength]])"],
+        };
+        let args = args.into_vec();
+        let result = (|| {
+            let first = args.first().cloned().unwrap_or(mlua::Value::Nil);
+            let buffer = <ArrayBufferLike as mlua::FromLua>::from_lua(first.clone(), lua)
+                .map_err(|_| overloads.type_error(1, "ArrayBuffer or SharedArrayBuffer", &first))?;
+            overloads.check_count(&args, 3)?;
+            let offset = overloads.index(args.get(1), 2, "byteOffset")?;
+            let length = overloads.index(args.get(2), 3, "byteLength")?;
+            Ok(DataView::new(buffer, offset, length)?)
+        })();
+        outcome(lua, result)
+    })?;
+    memory_table.raw_set("DataView", raising(lua, data_view)?)?;
+
+    memory_table.raw_set("Atomics", atomics::create_table(lua)?)?;
+    memory_table.raw_set("struct", pack::create_table(lua)?)?;
+
+    let struct_layout = lua.create_function(|lua, args: mlua::MultiValue| {
+        let overloads = Overloads {
+            name: "Struct",
+            signatures: &["(fields [, { packed = false }])"],
+        };
+        let args = args.into_vec();
+        outcome(lua, struct_view::construct(&overloads, &args))
+    })?;
+    memory_table.raw_set("Struct", raising(lua, struct_layout)?)?;
+    memory_table.raw_set("toError", lua.create_function(to_error)?)?;
+
+    memory_table.raw_set(
+        "Int128",
+        lua.create_function(|_, args| int128::construct::<Int128>(args))?,
+    )?;
+    memory_table.raw_set(
+        "UInt128",
+        lua.create_function(|_, args| int128::construct::<UInt128>(args))?,
     )?;
 
     add_typed_array!(lua, memory_table, i8);
     add_typed_array!(lua, memory_table, u8);
+    add_typed_array!(lua, memory_table, typed_array::ClampedU8);
 
     add_typed_array!(lua, memory_table, i16);
     add_typed_array!(lua, memory_table, u16);
@@ -118,8 +453,1515 @@ pub fn create_table<'lua>(lua: &'lua mlua::Lua) -> mlua::Result<mlua::Table<'lua
     add_typed_array!(lua, memory_table, i64);
     add_typed_array!(lua, memory_table, u64);
 
+    add_typed_array!(lua, memory_table, i128);
+    add_typed_array!(lua, memory_table, u128);
+
+    add_typed_array!(lua, memory_table, half::f16);
+    add_typed_array!(lua, memory_table, half::bf16);
+
     add_typed_array!(lua, memory_table, f32);
     add_typed_array!(lua, memory_table, f64);
 
     Ok(memory_table)
 }
+
+/// Adds `ArrayBuffer.mmap(path, options)` to a memory table built by
+/// [`create_table`], which leaves it out.
+///
+/// `options` may set `mode`, `offset` and `length`. With `mode = 'rw'` writes
+/// through views go to the file. With the default `mode = 'r'` the file is
+/// mapped read-only, and writing through a view raises a `TypeError`.
+///
+/// # Safety
+///
+/// Views access the mapped pages directly, so the file must not change size
+/// or be mapped writable elsewhere while a buffer maps it: truncating it makes
+/// accesses past its new end raise `SIGBUS`, and a second `'rw'` mapping
+/// aliases memory Rust treats as exclusively borrowed. Scripts can do both,
+/// with `io.open(path, 'w')` or another `mmap` call, so only enable this for
+/// scripts trusted not to.
+pub unsafe fn enable_mmap(lua: &mlua::Lua, memory_table: &mlua::Table) -> mlua::Result<()> {
+    let array_buffer: mlua::Table = memory_table.raw_get("ArrayBuffer")?;
+    array_buffer.raw_set(
+        "mmap",
+        lua.create_function(|_, args: (String, mlua::MultiValue)| {
+            let overloads = Overloads {
+                name: "mmap",
+                signatures: &["(path [, { mode = 'r', offset = 0, length = n }])"],
+            };
+            let (path, options) = (args.0, args.1.into_vec());
+            overloads.check_count(&options, 1)?;
+            let (mode, offset, length) = match options.first() {
+                None | Some(mlua::Value::Nil) => (None, None, None),
+                Some(mlua::Value::Table(options)) => (
+                    match options.get::<_, mlua::Value>("mode")? {
+                        mlua::Value::Nil => None,
+                        mlua::Value::String(mode) => Some(mode.to_str()?.to_owned()),
+                        value => return Err(overloads.type_error(2, "string", &value)),
+                    },
+                    overloads.index(
+                        Some(&options.get::<_, mlua::Value>("offset")?),
+                        2,
+                        "offset",
+                    )?,
+                    overloads.index(
+                        Some(&options.get::<_, mlua::Value>("length")?),
+                        2,
+                        "length",
+                    )?,
+                ),
+                Some(value) => return Err(overloads.type_error(2, "table", value)),
+            };
+            let writable = match mode.as_deref() {
+                None | Some("r") => false,
+                Some("rw") => true,
+                Some(mode) => {
+                    return Err(overloads.range_error(
+                        Some(2),
+                        format!("invalid mode '{}', expected 'r' or 'rw'", mode),
+                    ))
+                }
+            };
+            // SAFETY: the host accepted the contract of `enable_mmap`.
+            let buffer = unsafe {
+                ArrayBuffer::mmap(
+                    std::path::Path::new(&path),
+                    writable,
+                    offset.unwrap_or(0) as u64,
+                    length,
+                )
+            };
+            Ok(buffer?)
+        })?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypedArray;
+
+    fn lua() -> mlua::Lua {
+        let lua = mlua::Lua::new();
+        lua.globals()
+            .set("memory", super::create_table(&lua).unwrap())
+            .unwrap();
+        lua
+    }
+
+    #[test]
+    fn shared_storage() {
+        let buffer = super::ArrayBuffer::new(8).unwrap();
+        assert_eq!(buffer.write_from(6, &[1, 2]), Some(()));
+        assert_eq!(buffer.write_from(7, &[1, 2]), None);
+        let mut bytes = [0; 3];
+        assert_eq!(buffer.read_into(6, &mut bytes), None);
+        assert_eq!(bytes, [0; 3]);
+        assert_eq!(buffer.read_into(5, &mut bytes), Some(()));
+        assert_eq!(bytes, [0, 1, 2]);
+
+        let lua = lua();
+        lua.globals().set("buffer", buffer).unwrap();
+        let bytes = lua
+            .load(
+                "local words = memory.UInt16Array(buffer)
+                local bytes = memory.UInt8Array(buffer)
+                words[1] = 0x0102
+                bytes[3] = words[1] & 0xff
+                words[4] = bytes[1] + bytes[2]
+                local t = {}
+                for i = 1, #bytes do
+                    t[i] = math.tointeger(bytes[i])
+                end
+                return table.concat(t, ' ')",
+            )
+            .eval::<String>()
+            .unwrap();
+        let expected = if cfg!(target_endian = "little") {
+            "2 1 2 0 0 0 3 0"
+        } else {
+            "1 2 2 0 0 0 0 3"
+        };
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn resizable_buffers() {
+        let lua = lua();
+        let (buffer, tracking, fixed) = lua
+            .load(
+                "local buffer = memory.ArrayBuffer(4, { maxByteLength = 8 })
+                local tracking = memory.UInt8Array(buffer)
+                tracking[4] = 7
+                return buffer, tracking, memory.UInt8Array(buffer, 0, 4)",
+            )
+            .eval::<(super::ArrayBuffer, super::TypedArray, super::TypedArray)>()
+            .unwrap();
+        assert!(buffer.is_resizable());
+        assert_eq!(buffer.max_len(), 8);
+        assert_eq!(tracking.len(), 4);
+
+        buffer.resize(8).map_err(|err| err.message()).unwrap();
+        assert_eq!(tracking.len(), 8);
+        assert_eq!(fixed.len(), 4);
+        assert_eq!(tracking.get_number(3), Some(7.0));
+        assert_eq!(tracking.get_number(7), Some(0.0));
+
+        buffer.resize(2).map_err(|err| err.message()).unwrap();
+        assert_eq!(tracking.len(), 2);
+        assert_eq!(fixed.len(), 0, "fixed-length view past the end");
+
+        let moved = buffer
+            .transfer(Some(6))
+            .map_err(|err| err.message())
+            .unwrap();
+        assert!(buffer.is_detached());
+        assert_eq!(moved.len(), 6);
+        assert_eq!(moved.max_len(), 8);
+
+        assert!(moved.resize(9).is_err(), "above maxByteLength");
+        assert!(
+            super::ArrayBuffer::new(2).unwrap().resize(1).is_err(),
+            "fixed-length buffer"
+        );
+        assert!(buffer.transfer(None).is_err(), "detached buffer");
+        assert!(lua
+            .load("memory.ArrayBuffer(2, { maxByteLength = 1 })")
+            .exec()
+            .is_err());
+        assert!(
+            lua.load("memory.ArrayBuffer(math.maxinteger, { maxByteLength = math.maxinteger })")
+                .exec()
+                .is_err(),
+            "allocation failure"
+        );
+    }
+
+    #[test]
+    fn detached_buffers() {
+        let lua = lua();
+        lua.load(
+            "local buffer = memory.ArrayBuffer(8)
+            local view = memory.Int16Array(buffer, 2)
+            local data = memory.DataView(buffer)
+            buffer:detach()
+            assert(buffer.detached, 'buffer.detached')
+            assert(buffer.byteLength == 0, 'buffer.byteLength')
+            assert(#view == 0, '#view')
+            assert(view.byteOffset == 0, 'view.byteOffset')
+            assert(view.byteLength == 0, 'view.byteLength')
+            assert(data.byteLength == 0, 'data.byteLength')
+            for name, call in pairs({
+                read = function() return view[1] end,
+                write = function() view[1] = 3 end,
+                getInt8 = function() return data:getInt8(0) end,
+                construct = function() return memory.UInt8Array(buffer) end,
+            }) do
+                local ok, err = pcall(call)
+                assert(not ok and tostring(err):find('detached'), name .. ': ' .. tostring(err))
+            end
+            local kept = memory.ArrayBuffer(2, { maxByteLength = math.maxinteger })
+            memory.UInt8Array(kept)[1] = 9
+            assert(not pcall(kept.transfer, kept, math.maxinteger), 'transfer past memory')
+            assert(not kept.detached, 'failed transfer detached the buffer')
+            assert(memory.UInt8Array(kept)[1] == 9, 'failed transfer lost the contents')",
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn subarray_slice() {
+        let lua = lua();
+        lua.load(
+            "local function list(array)
+                local t = {}
+                for i = 1, #array do
+                    t[i] = math.tointeger(array[i])
+                end
+                return table.concat(t, ',')
+            end
+            local array = memory.Int16Array(5)
+            for i = 1, 5 do
+                array[i] = i
+            end
+            local window = array:subarray(2, -2)
+            local copy = array:slice(-2)
+            window[1] = 20
+            copy[1] = 40
+            assert(list(array) == '1,20,3,4,5', 'array: ' .. list(array))
+            assert(list(window) == '20,3,4', 'window: ' .. list(window))
+            assert(window.byteOffset == 2, 'window.byteOffset')
+            assert(list(copy) == '40,5', 'copy: ' .. list(copy))
+            assert(#array:subarray(4, 2) == 0, 'reversed bounds')
+            assert(#array:slice(10) == 0, 'begin past the end')
+            assert(list(array:slice(-100, 1)) == '1', 'begin before the start')
+
+            local resizable = memory.ArrayBuffer(4, { maxByteLength = 8 })
+            local tracking = memory.UInt8Array(resizable)
+            local tail = tracking:subarray(2)
+            local bounded = tracking:subarray(2, -1)
+            resizable:resize(8)
+            assert(#tail == 7, 'subarray without end tracks the length: ' .. #tail)
+            assert(#bounded == 3, 'subarray with end keeps its length: ' .. #bounded)
+            assert(#memory.UInt8Array(4):subarray(2) == 3, 'subarray of a fixed-length array')
+
+            local buffer = memory.ArrayBuffer(6)
+            local bytes = memory.UInt8Array(buffer)
+            for i = 1, 6 do
+                bytes[i] = i
+            end
+            local sliced = buffer:slice(2, 4)
+            bytes[2] = 0
+            assert(list(memory.UInt8Array(sliced)) == '2,3,4', 'ArrayBuffer:slice')
+            buffer:detach()
+            assert(not pcall(buffer.slice, buffer), 'slice of a detached buffer')",
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn functional_methods() {
+        let lua = lua();
+        lua.load(
+            "local a = memory.Int32Array(5)
+            for i, v in ipairs({ 3, 1, 4, 1, 5 }) do
+                a[i] = v
+            end
+            local seen = {}
+            a:forEach(function(v, i)
+                seen[#seen + 1] = i .. '=' .. math.tointeger(v)
+            end)
+            local function check(got, expected, what)
+                assert(got == expected, what .. ': got ' .. tostring(got))
+            end
+            check(table.concat(seen, ','), '1=3,2=1,3=4,4=1,5=5', 'forEach')
+            check(a:map(function(v, i) return v * i end):join(','), '3,2,12,4,25', 'map')
+            check(a:filter(function(v) return v > 1 end):join(','), '3,4,5', 'filter')
+            check(a:reduce(function(acc, v) return acc .. math.tointeger(v) end, ''), '31415', 'reduce')
+            check(
+                a:reduceRight(function(acc, v) return acc .. math.tointeger(v) end, ''),
+                '51413',
+                'reduceRight'
+            )
+            check(a:reduce(function(acc, v) return acc + v end), 14, 'reduce without initial value')
+            check(a:every(function(v) return v > 0 end), true, 'every')
+            check(a:some(function(v) return v > 5 end), false, 'some')
+            check(a:find(function(v) return v > 3 end), 4, 'find')
+            check(a:findIndex(function(v) return v > 3 end), 3, 'findIndex')
+            check(a:findLast(function(v) return v < 4 end), 1, 'findLast')
+            check(a:findIndex(function() return false end), nil, 'findIndex without match')
+            check(a:indexOf(1), 2, 'indexOf')
+            check(a:lastIndexOf(1), 4, 'lastIndexOf')
+            check(a:indexOf(1, 3), 4, 'indexOf from')
+            check(a:indexOf(9), nil, 'indexOf without match')
+            check(a:includes(4), true, 'includes')
+            local nan = memory.Float32Array(1)
+            nan[1] = 0 / 0
+            check(nan:includes(0 / 0), true, 'includes NaN')
+            check(nan:indexOf(0 / 0), nil, 'indexOf NaN')
+            local wide = memory.Int64Array.of(math.maxinteger - 1)
+            check(wide:indexOf(math.maxinteger), nil, 'indexOf compares 64-bit values exactly')
+            check(wide:includes(math.maxinteger), false, 'includes compares 64-bit values exactly')
+            check(wide:indexOf(math.maxinteger - 1), 1, 'indexOf of a 64-bit value')
+            local unsigned = memory.UInt64Array.of(-1)
+            check(unsigned:indexOf(unsigned[1]), 1, 'indexOf of a value read from UInt64Array')
+            check(unsigned:indexOf(2^64 - 1), nil, 'indexOf of a float above maxinteger')
+            local int128 = memory.Int128Array.of(memory.Int128(-5), 2^100)
+            check(int128:indexOf(memory.Int128(-5)), 1, 'indexOf of an Int128')
+            check(int128:lastIndexOf(2^100), 2, 'lastIndexOf of a float in Int128Array')
+            check(memory.UInt128Array.of(1):includes(memory.UInt128(1)), true, 'includes of a UInt128')
+            check(memory.Float64Array.of(2.5, 3):indexOf(memory.Int128(3)), 2, 'Int128 in Float64Array')
+            check(a:reverse():join(','), '5,1,4,1,3', 'reverse')
+            check(a:join('-'), '5-1-4-1-3', 'join')
+            local empty = memory.Int8Array(0)
+            local ok, err = pcall(empty.reduce, empty, function() end)
+            assert(not ok and tostring(err):find('reduce of empty array'), tostring(err))",
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn sorting() {
+        let lua = lua();
+        lua.load(
+            "local function of(constructor, ...)
+                local values = { ... }
+                local array = memory[constructor](#values)
+                for i, v in ipairs(values) do
+                    array[i] = v
+                end
+                return array
+            end
+            local function check(got, expected, what)
+                assert(got == expected, what .. ': got ' .. tostring(got))
+            end
+            local floats = of('Float64Array', 3, 0 / 0, -1, 0, -0.0, 2)
+            check(floats:toSorted():join(','), '-1,-0,0,2,3,NaN', 'toSorted')
+            check(floats:join(','), '3,NaN,-1,0,-0,2', 'toSorted leaves the array')
+            floats:sort()
+            check(floats:join(','), '-1,-0,0,2,3,NaN', 'sort')
+            local ints = of('Int8Array', 5, 3, 9, 1)
+            local descending = function(a, b) return a > b end
+            check(ints:toSorted(descending):join(','), '9,5,3,1', 'toSorted with comparator')
+            check(rawequal(ints:sort(descending), ints), true, 'sort returns the array')
+            check(ints:join(','), '9,5,3,1', 'sort with comparator')
+            local unsigned = of('UInt64Array', 2^63, 1)
+            unsigned:sort()
+            check(unsigned:join(','), '1,9223372036854775808', 'unsigned order')
+            ints[1] = 0
+            check(pcall(ints.sort, ints, function() error('boom') end), false, 'failing comparator')
+            check(ints:join(','), '0,5,3,1', 'failing comparator leaves the array')
+            local ascending = function(a, b) return a < b end
+            local wide = memory.Int64Array.of(math.maxinteger, math.maxinteger - 1)
+            check(wide:sort(ascending)[1], math.maxinteger - 1, 'comparator gets exact integers')
+            local seen = {}
+            memory.UInt64Array.of(-1, 1):sort(function(a, b)
+                seen[#seen + 1] = math.type(a) .. ' ' .. a
+                return ascending(a, b)
+            end)
+            check(seen[1], 'integer 1', 'comparator gets UInt64 values like indexing does')
+            check(seen[2], nil, 'a single comparison')
+            local int128 = memory.Int128Array.of(2, 1):sort(function(a, b)
+                assert(getmetatable(a) == getmetatable(memory.Int128(0)), 'Int128 userdata')
+                return a < b
+            end)
+            check(int128:join(','), '1,2', 'comparator over Int128 values')
+            local buffer = memory.ArrayBuffer(4, { maxByteLength = 4 })
+            local shrinking = memory.UInt8Array(buffer)
+            shrinking:set({ 4, 3, 2, 1 })
+            shrinking:sort(function(a, b)
+                buffer:resize(2)
+                return a < b
+            end)
+            check(shrinking:join(','), '1,2', 'comparator shrinking the buffer')",
+        )
+        .exec()
+        .unwrap();
+    }
+
+    /// Assigns each of `inputs` (Lua expressions) to a fresh array built with
+    /// the `constructor` registered in the memory table, then checks both the
+    /// number read back by `get_number` and the value seen through `__index`,
+    /// taking negative integers read from a `UInt64Array` as unsigned.
+    fn round_trip(constructor: &str, inputs: &[&str], expected: &[f64]) {
+        let lua = lua();
+        let array = lua
+            .load(&format!(
+                "local inputs = {{ {} }}
+                local array = memory.{}(#inputs)
+                for i, v in ipairs(inputs) do array[i] = v end
+                return array",
+                inputs.join(", "),
+                constructor
+            ))
+            .eval::<mlua::AnyUserData>()
+            .unwrap();
+        let indexed = lua
+            .load(
+                "local array = ...
+                local t = {}
+                for i = 1, #array do
+                    local v = array[i]
+                    if type(v) == 'userdata' then
+                        v = v:tonumber()
+                    elseif array.name == 'UInt64Array' and v < 0 then
+                        v = v + 2.0^64
+                    end
+                    t[i] = v
+                end
+                return t",
+            )
+            .call::<_, Vec<mlua::Number>>(array.clone())
+            .unwrap();
+        let array = array.borrow::<TypedArray>().unwrap();
+        assert_eq!(array.len(), expected.len(), "{constructor}");
+        for (i, expected) in expected.iter().enumerate() {
+            let number = array.get_number(i).unwrap();
+            let same = |n: f64| n == *expected || (n.is_nan() && expected.is_nan());
+            assert!(
+                same(number),
+                "{constructor}[{i}]: got {number}, expected {expected}"
+            );
+            let number = indexed[i];
+            assert!(
+                same(number),
+                "{constructor}[{i}]: indexed {number}, expected {expected}"
+            );
+        }
+    }
+
+    const SPECIAL: [&str; 3] = ["0/0", "math.huge", "-math.huge"];
+
+    fn inputs<'a>(values: &[&'a str]) -> Vec<&'a str> {
+        values.iter().copied().chain(SPECIAL).collect()
+    }
+
+    #[test]
+    fn int8() {
+        round_trip(
+            "Int8Array",
+            &inputs(&["-128", "127", "-1"]),
+            &[-128.0, 127.0, -1.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn uint8() {
+        round_trip(
+            "UInt8Array",
+            &inputs(&["0", "255", "-1"]),
+            &[0.0, 255.0, 255.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn uint8_clamped() {
+        round_trip(
+            "UInt8ClampedArray",
+            &inputs(&["-1", "300", "0.5", "1.5", "254.5"]),
+            &[0.0, 255.0, 0.0, 2.0, 254.0, 0.0, 255.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn int16() {
+        round_trip(
+            "Int16Array",
+            &inputs(&["-32768", "32767", "-1"]),
+            &[-32768.0, 32767.0, -1.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn uint16() {
+        round_trip(
+            "UInt16Array",
+            &inputs(&["0", "65535", "-1"]),
+            &[0.0, 65535.0, 65535.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn int32() {
+        round_trip(
+            "Int32Array",
+            &inputs(&["-2147483648", "2147483647", "-1"]),
+            &[-2147483648.0, 2147483647.0, -1.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn uint32() {
+        round_trip(
+            "UInt32Array",
+            &inputs(&["0", "4294967295", "-1"]),
+            &[0.0, 4294967295.0, 4294967295.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn int64() {
+        round_trip(
+            "Int64Array",
+            &inputs(&["math.mininteger", "math.maxinteger", "-1"]),
+            &[i64::MIN as f64, i64::MAX as f64, -1.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn uint64() {
+        round_trip(
+            "UInt64Array",
+            &inputs(&["0", "math.maxinteger", "-1"]),
+            &[0.0, i64::MAX as f64, u64::MAX as f64, 0.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn int128() {
+        round_trip(
+            "Int128Array",
+            &inputs(&[
+                "memory.Int128('-170141183460469231731687303715884105728')",
+                "memory.Int128('0x7fffffffffffffffffffffffffffffff')",
+                "-1",
+                "2^100",
+            ]),
+            &[
+                i128::MIN as f64,
+                i128::MAX as f64,
+                -1.0,
+                2f64.powi(100),
+                0.0,
+                0.0,
+                0.0,
+            ],
+        );
+    }
+
+    #[test]
+    fn uint128() {
+        round_trip(
+            "UInt128Array",
+            &inputs(&["0", "memory.UInt128(-1, -1)", "-1", "2^127 * 1.5"]),
+            &[
+                0.0,
+                u128::MAX as f64,
+                u128::MAX as f64,
+                2f64.powi(127) * 1.5,
+                0.0,
+                0.0,
+                0.0,
+            ],
+        );
+    }
+
+    #[test]
+    fn float16() {
+        round_trip(
+            "Float16Array",
+            // 65520 is halfway to the next power of two and rounds to even,
+            // 2^-24 is the smallest subnormal and 1 + 2^-11 a tie below 1.
+            &inputs(&["-65504", "65504", "-1", "65520", "2^-24", "1 + 2^-11"]),
+            &[
+                -65504.0,
+                65504.0,
+                -1.0,
+                f64::INFINITY,
+                2f64.powi(-24),
+                1.0,
+                f64::NAN,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ],
+        );
+    }
+
+    #[test]
+    fn bfloat16() {
+        round_trip(
+            "BFloat16Array",
+            &inputs(&["-1", "1 + 2^-8", "1 + 3 * 2^-8", "3.3895313892515355e38"]),
+            &[
+                -1.0,
+                1.0,
+                1.0 + 2f64.powi(-6),
+                3.3895313892515355e38,
+                f64::NAN,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ],
+        );
+    }
+
+    #[test]
+    fn float32() {
+        round_trip(
+            "Float32Array",
+            &inputs(&["-3.4028234663852886e38", "3.4028234663852886e38", "-1"]),
+            &[
+                f32::MIN.into(),
+                f32::MAX.into(),
+                -1.0,
+                f64::NAN,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ],
+        );
+    }
+
+    #[test]
+    fn float64() {
+        round_trip(
+            "Float64Array",
+            &inputs(&["-1.7976931348623157e308", "1.7976931348623157e308", "-1"]),
+            &[
+                f64::MIN,
+                f64::MAX,
+                -1.0,
+                f64::NAN,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ],
+        );
+    }
+
+    #[test]
+    fn iteration() {
+        let lua = lua();
+        lua.load(
+            "local array = memory.Int16Array(3)
+            for i = 1, 3 do
+                array[i] = i * 10
+            end
+            local function check(iterate, expected, what)
+                local visited = {}
+                for k, v in iterate() do
+                    visited[#visited + 1] = v == nil and tostring(k) or k .. '=' .. v
+                end
+                local got = table.concat(visited, ' ')
+                assert(got == expected, what .. ': got ' .. got)
+            end
+            check(function() return pairs(array) end, '1=10 2=20 3=30', 'pairs')
+            check(function() return ipairs(array) end, '1=10 2=20 3=30', 'ipairs')
+            check(function() return array:entries() end, '1=10 2=20 3=30', 'entries')
+            check(function() return array:keys() end, '1 2 3', 'keys')
+            check(function() return array:values() end, '10 20 30', 'values')
+            local buffer = memory.ArrayBuffer(4, { maxByteLength = 8 })
+            local visited = 0
+            for i in memory.UInt8Array(buffer):keys() do
+                visited = visited + 1
+                if i == 1 then buffer:resize(2) end
+            end
+            assert(visited == 2, 'iteration stops when the buffer shrinks')",
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn constructor_errors() {
+        let lua = lua();
+        let check = |code: &str, kind: &str, argument: Option<usize>| {
+            let (got_kind, got_argument, message) = lua
+                .load(&format!(
+                    "local ok, err = pcall(function() return {} end)
+                    assert(not ok, 'expected an error')
+                    return err.kind, err.argument, err.message",
+                    code
+                ))
+                .eval::<(String, Option<usize>, String)>()
+                .unwrap();
+            assert_eq!(
+                (got_kind.as_str(), got_argument),
+                (kind, argument),
+                "{message}"
+            );
+        };
+        check("memory.Float32Array(true)", "TypeError", Some(1));
+        check("memory.Float32Array(-1)", "RangeError", Some(1));
+        check(
+            "memory.Int32Array(memory.ArrayBuffer(8), 2)",
+            "RangeError",
+            Some(2),
+        );
+        check(
+            "memory.UInt32Array(memory.ArrayBuffer(6))",
+            "RangeError",
+            Some(1),
+        );
+        check(
+            "memory.Int32Array(memory.ArrayBuffer(8), 0, 1, 2)",
+            "TypeError",
+            Some(4),
+        );
+        check(
+            "memory.ArrayBuffer(4, { maxByteLength = 2 })",
+            "RangeError",
+            None,
+        );
+        check("memory.DataView({})", "TypeError", Some(1));
+        // Byte lengths that overflow usize are rejected rather than wrapped.
+        check("memory.Float64Array(2^61)", "RangeError", None);
+        check(
+            "memory.UInt32Array(memory.ArrayBuffer(8), 0, 2^62)",
+            "RangeError",
+            None,
+        );
+    }
+
+    #[test]
+    fn method_errors() {
+        let lua = lua();
+        let check = |code: &str, kind: &str, reason: &str| {
+            let (got_kind, got_reason, message) = lua
+                .load(&format!(
+                    "local ok, err = pcall(function() {} end)
+                    assert(not ok, 'expected an error')
+                    err = memory.toError(err)
+                    return err.kind, err.reason, err.message",
+                    code
+                ))
+                .eval::<(String, String, String)>()
+                .unwrap();
+            assert_eq!(
+                (got_kind.as_str(), got_reason.as_str()),
+                (kind, reason),
+                "{message}"
+            );
+        };
+        check(
+            "local a = memory.Int8Array(4) a.overflow = 'error' a[1] = 1000",
+            "RangeError",
+            "Overflow",
+        );
+        check("memory.Int8Array(4)[5] = 1", "RangeError", "OutOfBounds");
+        check(
+            "local a = memory.Int8Array(4) a.buffer:detach() return a[1]",
+            "TypeError",
+            "Detached",
+        );
+        check(
+            "memory.DataView(memory.ArrayBuffer(4)):getInt32(2)",
+            "RangeError",
+            "OutOfBounds",
+        );
+        check(
+            "memory.ArrayBuffer(4):resize(8)",
+            "TypeError",
+            "NotResizable",
+        );
+        check(
+            "memory.Int32Array(memory.ArrayBuffer(8), 2)",
+            "RangeError",
+            "Misaligned",
+        );
+        assert!(matches!(
+            lua.load("return memory.toError(select(2, pcall(error, 'plain')))")
+                .eval::<mlua::Value>(),
+            Ok(mlua::Value::Nil)
+        ));
+    }
+
+    #[test]
+    fn typed_views() {
+        let lua = lua();
+        for constructor in ["ArrayBuffer", "SharedArrayBuffer"] {
+            let mut array = lua
+                .load(&format!(
+                    "return memory.Float32Array(memory.{}(20), 4, 3):fill(1.5)",
+                    constructor
+                ))
+                .eval::<super::TypedArray>()
+                .unwrap();
+            let view = array.as_typed::<f32>().unwrap();
+            assert_eq!(view.len(), 3);
+            assert_eq!(view.iter().sum::<f32>(), 4.5);
+            assert_eq!(view.get(3), None);
+            drop(view);
+            assert!(matches!(
+                array.as_typed::<f64>(),
+                Err(super::Error::KindMismatch { .. })
+            ));
+            let mut view = array.as_typed_mut::<f32>().unwrap();
+            view.copy_from_slice(&[1.0, 2.0, 3.0]);
+            view.set(0, -1.0).unwrap();
+            assert_eq!(view.to_vec(), [-1.0, 2.0, 3.0]);
+            drop(view);
+            assert_eq!(array.get_number(1), Some(2.0));
+            assert_eq!(array.buffer().len(), 20);
+        }
+        let clamped = lua
+            .load("return memory.UInt8ClampedArray(4)")
+            .eval::<super::TypedArray>()
+            .unwrap();
+        assert!(matches!(
+            clamped.as_typed::<u8>(),
+            Err(super::Error::KindMismatch { .. })
+        ));
+        assert_eq!(clamped.as_typed::<super::ClampedU8>().unwrap().len(), 4);
+        let mut shrunk = lua
+            .load(
+                "local buffer = memory.ArrayBuffer(8, { maxByteLength = 8 })
+                local view = memory.UInt8Array(buffer, 4, 4)
+                buffer:resize(2)
+                return view",
+            )
+            .eval::<super::TypedArray>()
+            .unwrap();
+        assert!(shrunk.as_typed::<u8>().unwrap().is_empty());
+        assert!(shrunk.as_typed_mut::<u8>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn string_conversion() {
+        let lua = lua();
+        let (len, whole, tail, view) = lua
+            .load(
+                "local buffer = memory.ArrayBuffer.fromString('a\\0b\\0')
+                local view = memory.UInt16Array(buffer, 2, 1)
+                return buffer.byteLength, buffer:toString(), buffer:toString(2), view:toString()",
+            )
+            .eval::<(usize, mlua::String, mlua::String, mlua::String)>()
+            .unwrap();
+        assert_eq!(len, 4);
+        assert_eq!(whole.as_bytes(), b"a\0b\0");
+        assert_eq!(tail.as_bytes(), b"b\0");
+        assert_eq!(view.as_bytes(), b"b\0");
+        let (out_of_bounds, allocation) = lua
+            .load(
+                "local buffer = memory.ArrayBuffer.fromString('abc')
+                local _, out_of_bounds = pcall(buffer.toString, buffer, 1, 3)
+                local _, allocation = pcall(memory.ArrayBuffer, math.maxinteger)
+                return memory.toError(out_of_bounds).reason, allocation.reason",
+            )
+            .eval::<(String, String)>()
+            .unwrap();
+        assert_eq!(out_of_bounds, "OutOfBounds");
+        assert_eq!(allocation, "Allocation");
+        let (shrunk, round_trip) = lua
+            .load(
+                "local buffer = memory.ArrayBuffer(8, { maxByteLength = 8 })
+                local view = memory.UInt8Array(buffer, 4, 4)
+                buffer:resize(2)
+                local huge = string.rep('ab\0', 1 << 22)
+                local copy = memory.ArrayBuffer.fromString(huge)
+                return view:toString(), copy.byteLength == #huge and copy:toString() == huge",
+            )
+            .eval::<(mlua::String, bool)>()
+            .unwrap();
+        assert_eq!(shrunk.as_bytes(), b"");
+        assert!(round_trip);
+    }
+
+    #[test]
+    fn base64_hex() {
+        let lua = lua();
+        lua.load(
+            "local function check(got, expected, what)
+                assert(got == expected, what .. ': got ' .. tostring(got))
+            end
+            local bytes = memory.UInt8Array.fromHex('FBFF00')
+            local short = memory.UInt8Array.of(102, 111)
+            check(bytes:toBase64(), '+/8A', 'toBase64')
+            check(bytes:toBase64({ alphabet = 'base64url' }), '-_8A', 'base64url alphabet')
+            check(short:toBase64(), 'Zm8=', 'padding')
+            check(short:toBase64({ omitPadding = true }), 'Zm8', 'omitPadding')
+            check(short:toHex(), '666f', 'toHex')
+            check(memory.UInt8Array.fromBase64(' Zm9v\\nYg== '):toHex(), '666f6f62', 'whitespace')",
+        )
+        .exec()
+        .unwrap();
+        let (read, written, bytes) = lua
+            .load(
+                "local target = memory.UInt8Array(4)
+                local read, written = target:setFromBase64('Zm9vYmFy')
+                return read, written, target:toHex()",
+            )
+            .eval::<(usize, usize, String)>()
+            .unwrap();
+        assert_eq!((read, written, bytes.as_str()), (4, 3, "666f6f00"));
+        let (read, written) = lua
+            .load(
+                "return memory.UInt8Array(8):setFromBase64('Zm9vYg', \
+                    { lastChunkHandling = 'stop-before-partial' })",
+            )
+            .eval::<(usize, usize)>()
+            .unwrap();
+        assert_eq!((read, written), (4, 3));
+        lua.load(
+            "local function check(got, expected, what)
+                assert(got == expected, what .. ': got ' .. tostring(got))
+            end
+            local _, strict = pcall(memory.UInt8Array.fromBase64, 'Zm9=', \
+                { lastChunkHandling = 'strict' })
+            check(memory.toError(strict).kind, 'SyntaxError', 'strict last chunk')
+            local _, hex = pcall(memory.UInt8Array.fromHex, 'abc')
+            check(memory.toError(hex).reason, 'Syntax', 'odd hex length')
+            local target = memory.UInt8Array(2)
+            local _, partial = pcall(target.setFromHex, target, '01zz')
+            check(memory.toError(partial).message, 'invalid hex string at offset 2', 'bad hex digit')
+            check(target:toHex(), '0100', 'bytes before the bad digit')
+            local words = memory.UInt16Array(1)
+            local _, kind = pcall(words.toHex, words)
+            check(memory.toError(kind).reason, 'KindMismatch', 'toHex of UInt16Array')",
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn struct_pack() {
+        let lua = lua();
+        lua.load(
+            "local buffer = memory.ArrayBuffer(40)
+            local format = '!<b i4 >h z s1 d'
+            local args = { -1, 70000, 513, 'id', 'xyz', 0.5 }
+            local written = memory.struct.pack(format, buffer, 2, table.unpack(args))
+            assert(buffer:toString(2, written) == string.pack(format, table.unpack(args)),
+                'pack matches string.pack')
+            local values = table.pack(memory.struct.unpack(format, buffer, 2))
+            for i, expected in ipairs(args) do
+                assert(values[i] == expected, 'value ' .. i .. ': got ' .. tostring(values[i]))
+            end
+            assert(values[7] == written, 'unpack returns the next offset')",
+        )
+        .exec()
+        .unwrap();
+        let (size, written, a, b, consumed) = lua
+            .load(
+                "local header = memory.struct.compile('>I2 I2')
+                local buffer = memory.ArrayBuffer(4)
+                return header.size, header:pack(buffer, 0, 80, 443), header:unpack(buffer)",
+            )
+            .eval::<(usize, usize, u16, u16, usize)>()
+            .unwrap();
+        assert_eq!((size, written, a, b, consumed), (4, 4, 80, 443, 4));
+        lua.load(
+            "local function check(got, expected, what)
+                assert(got == expected, what .. ': got ' .. tostring(got))
+            end
+            local buffer = memory.ArrayBuffer(2)
+            local _, syntax = pcall(memory.struct.size, 'i4 q')
+            check(memory.toError(syntax).message, 'invalid pack format string at offset 3',
+                'unknown option')
+            local _, overflow = pcall(memory.struct.pack, 'B', buffer, 0, 256)
+            check(memory.toError(overflow).argument, 4, 'overflowing value')
+            local _, bounds = pcall(memory.struct.unpack, 'i4', buffer)
+            check(memory.toError(bounds).reason, 'OutOfBounds', 'unpack past the end')
+            local format = memory.struct.compile('i4')
+            for what, call in pairs({
+                ['struct.pack'] = function() return memory.struct.pack(format, buffer, -1, 1) end,
+                ['struct.unpack'] = function() return memory.struct.unpack(format, buffer, -1) end,
+                ['pack'] = function() return format:pack(buffer, -1, 1) end,
+                ['unpack'] = function() return format:unpack(buffer, -1) end,
+            }) do
+                local _, err = pcall(call)
+                err = memory.toError(err)
+                check(err.kind, 'RangeError', what .. ' with a negative offset')
+                check(err.argument, what:find('struct') and 3 or 2, what .. ' offset argument')
+            end",
+        )
+        .exec()
+        .unwrap();
+        lua.load(
+            "local function check(got, expected, what)
+                got = tostring(got)
+                assert(got == expected, what .. ': got ' .. got)
+            end
+            local buffer = memory.ArrayBuffer(48)
+            local format = '<i16 I16 >i12'
+            local written = memory.struct.pack(format, buffer, 0, -2, -1, -3)
+            assert(buffer:toString(0, written) == string.pack(format, -2, -1, -3),
+                'wide integers match string.pack')
+            local a, b, c = memory.struct.unpack(format, buffer)
+            check(a, '-2', 'i16')
+            check(b, '18446744073709551615', 'I16')
+            check(c, '-3', 'i12')
+            assert(b == memory.UInt128(0, -1), 'I16 unpacks to a UInt128')
+            local _, overflow = pcall(memory.struct.pack, 'I9', buffer, 0, memory.UInt128(256, 0))
+            check(memory.toError(overflow).reason, 'Argument', 'I9 overflow')",
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn struct_views() {
+        let lua = lua();
+        let (aligned, packed) = lua
+            .load(
+                "local fields = { { 'a', 'u8' }, { 'b', 'f64' }, { 'c', 'u16' } }
+                local aligned = memory.Struct(fields)
+                local packed = memory.Struct(fields, { packed = true })
+                return { aligned.size, aligned.alignment, aligned:offsetOf('c') },
+                    { packed.size, packed.alignment, packed:offsetOf('c') }",
+            )
+            .eval::<(Vec<usize>, Vec<usize>)>()
+            .unwrap();
+        assert_eq!(aligned, [24, 8, 16]);
+        assert_eq!(packed, [11, 1, 9]);
+        let (len, x, id, raw, missing) = lua
+            .load(
+                "local Point = memory.Struct { { 'x', 'f32' }, { 'y', 'f32' }, { 'id', 'u32' } }
+                local buffer = memory.ArrayBuffer(Point.size * 2)
+                local points = Point(buffer)
+                points[1].x = 1.5
+                points[2] = { y = 2, id = 42 }
+                return #points, points[1].x, points[2].id, memory.UInt32Array(buffer)[6], points[3]",
+            )
+            .eval::<(usize, f64, u32, u32, Option<mlua::AnyUserData>)>()
+            .unwrap();
+        assert_eq!((len, x, id, raw), (2, 1.5, 42, 42));
+        assert!(missing.is_none());
+        let (field, kind) = lua
+            .load(
+                "local Point = memory.Struct { { 'x', 'f32' } }
+                local points = Point(memory.ArrayBuffer(8))
+                local _, field = pcall(function() return points[1].z end)
+                local _, kind = pcall(memory.Struct, { { 'x', 'f33' } })
+                return memory.toError(field).message, kind.kind",
+            )
+            .eval::<(String, String)>()
+            .unwrap();
+        assert_eq!(field, "struct has no field 'z'");
+        assert_eq!(kind, "TypeError");
+        lua.load(
+            "local function check(got, expected, what)
+                assert(got == expected, what .. ': got ' .. tostring(got))
+            end
+            local Pair = memory.Struct { { 'a', 'u16' }, { 'b', 'u16' } }
+            local buffer = memory.ArrayBuffer(16, { maxByteLength = 16 })
+            local tracking = Pair(buffer, 4)
+            local fixed = Pair(buffer, 8, 2)
+            check(tracking.length, 3, 'length-tracking length')
+            check(fixed.byteOffset, 8, 'fixed byteOffset')
+            check(tracking[0], nil, 'index 0')
+            check(tracking[4], nil, 'index past the end')
+            local _, err = pcall(function() tracking[4] = { a = 1 } end)
+            check(memory.toError(err).reason, 'OutOfBounds', 'assignment past the end')
+            buffer:resize(12)
+            check(fixed.length, 0, 'fixed view past the end of a shrunk buffer')
+            check(fixed.byteOffset, 0, 'byteOffset of a fixed view past the end')
+            check(fixed[1], nil, 'record of a fixed view past the end')
+            check(tracking.length, 2, 'length-tracking view of a shrunk buffer')
+            check(tracking.byteOffset, 4, 'byteOffset of a length-tracking view')
+            buffer:resize(2)
+            check(tracking.length, 0, 'length-tracking view past the end')
+            check(tracking.byteOffset, 0, 'byteOffset of a length-tracking view past the end')
+            local detached = Pair(buffer)
+            buffer:detach()
+            for what, value in pairs({ length = detached.length, byteOffset = detached.byteOffset,
+                byteLength = detached.byteLength }) do
+                check(value, 0, what .. ' of a detached view')
+            end
+            _, err = pcall(function() return detached[1] end)
+            check(memory.toError(err).reason, 'Detached', 'index of a detached view')
+            _, err = pcall(Pair, buffer)
+            check(memory.toError(err).reason, 'Detached', 'view of a detached buffer')",
+        )
+        .exec()
+        .unwrap();
+        let shared = lua
+            .load(
+                "local Pair = memory.Struct { { 'a', 'i32' }, { 'b', 'f64' } }
+                local buffer = memory.SharedArrayBuffer(Pair.size * 2)
+                local records = Pair(buffer)
+                records[2] = { a = -3, b = 0.25 }
+                assert(memory.Int32Array(buffer)[5] == -3, 'written through the shared buffer')
+                return buffer",
+            )
+            .eval::<super::SharedArrayBuffer>()
+            .unwrap();
+        let (a, b) = std::thread::spawn(move || {
+            let lua = self::lua();
+            lua.globals().set("buffer", shared).unwrap();
+            lua.load(
+                "local Pair = memory.Struct { { 'a', 'i32' }, { 'b', 'f64' } }
+                local record = Pair(buffer)[2]
+                return record.a, record.b",
+            )
+            .eval::<(i32, f64)>()
+            .unwrap()
+        })
+        .join()
+        .unwrap();
+        assert_eq!((a, b), (-3, 0.25));
+    }
+
+    #[test]
+    fn mapped_buffers() {
+        /// Removes the file even if an assertion fails.
+        struct TempFile(std::path::PathBuf);
+
+        impl Drop for TempFile {
+            fn drop(&mut self) {
+                let _ = std::fs::remove_file(&self.0);
+            }
+        }
+
+        let file =
+            TempFile(std::env::temp_dir().join(format!("memory-mmap-{}.bin", std::process::id())));
+        let path = &file.0;
+        std::fs::write(path, b"0123456789").unwrap();
+        let lua = mlua::Lua::new();
+        let memory = super::create_table(&lua).unwrap();
+        lua.globals().set("memory", memory.clone()).unwrap();
+        assert!(lua
+            .load("return memory.ArrayBuffer.mmap == nil")
+            .eval::<bool>()
+            .unwrap());
+        // SAFETY: nothing else truncates or maps the file during the test.
+        unsafe { super::enable_mmap(&lua, &memory) }.unwrap();
+        lua.globals().set("path", path.to_str().unwrap()).unwrap();
+        lua.load(
+            "local function check(got, expected, what)
+                assert(got == expected, what .. ': got ' .. tostring(got))
+            end
+            local function rejects(write, what)
+                local ok, err = pcall(write)
+                assert(not ok, what .. ': expected an error')
+                err = memory.toError(err)
+                check(err.kind .. ' ' .. err.reason, 'TypeError ReadOnly', what)
+            end
+            local readonly = memory.ArrayBuffer.mmap(path)
+            local bytes = memory.UInt8Array(readonly)
+            check(bytes[1], 48, 'read through a view')
+            rejects(function() bytes[1] = 65 end, 'index assignment')
+            rejects(function() bytes:fill(65) end, 'fill')
+            rejects(function() bytes:reverse() end, 'reverse')
+            rejects(function() bytes:setFromHex('41') end, 'setFromHex')
+            rejects(function() memory.DataView(readonly):setUInt8(0, 65) end, 'DataView')
+            rejects(function() memory.struct.pack('B', readonly, 1, 65) end, 'pack')
+            local _, err = pcall(readonly.flush, readonly)
+            check(memory.toError(err).reason, 'NotFlushable', 'flush')
+            local mapped = memory.ArrayBuffer.mmap(path, { mode = 'rw', offset = 2, length = 4 })
+            memory.DataView(mapped):setUInt32(0, 0x61626364)
+            mapped:flush()
+            check(mapped:toString(), 'abcd', 'rw mapping')
+            check(readonly:toString(), '01abcd6789', 'read-only mapping after writes to the file')
+            local function invalid(options, expected, what)
+                local ok, err = pcall(memory.ArrayBuffer.mmap, path, options)
+                assert(not ok, what .. ': expected an error')
+                err = memory.toError(err)
+                check(err.kind .. ' #' .. tostring(err.argument), expected, what)
+            end
+            invalid({ mode = 'w' }, 'RangeError #2', 'unknown mode')
+            invalid({ mode = true }, 'TypeError #2', 'mode of the wrong type')
+            invalid({ offset = -1 }, 'RangeError #2', 'negative offset')
+            invalid({ length = 1.5 }, 'RangeError #2', 'fractional length')
+            invalid('rw', 'TypeError #2', 'options of the wrong type')",
+        )
+        .exec()
+        .unwrap();
+        let contents = std::fs::read(path).unwrap();
+        assert_eq!(contents, b"01abcd6789");
+    }
+
+    #[test]
+    fn data_view() {
+        let lua = lua();
+        let (max, unsigned, high, wrapped, int16, float) = lua
+            .load(
+                "local view = memory.DataView(memory.ArrayBuffer(16), 2)
+                view:setInt64(0, math.maxinteger)
+                local max = view:getInt64(0)
+                view:setUInt64(0, 9007199254740993, true)
+                local unsigned = view:getUInt64(0, true)
+                view:setUInt64(0, -1)
+                local high = view:getUInt64(0)
+                view:setUInt8(0, 257)
+                local wrapped = view:getUInt8(0)
+                view:setInt16(0, -2, true)
+                view:setFloat32(4, 1.5)
+                return max, unsigned, high, wrapped, view:getInt16(0, true), view:getFloat32(4)",
+            )
+            .eval::<(mlua::Value, i64, i64, i64, i64, f32)>()
+            .unwrap();
+        assert_eq!(max, mlua::Value::Integer(i64::MAX));
+        assert_eq!(unsigned, 9007199254740993);
+        assert_eq!(high, -1);
+        assert_eq!((wrapped, int16, float), (1, -2, 1.5));
+        let reason = lua
+            .load(
+                "local _, err = pcall(function()
+                    return memory.DataView(memory.ArrayBuffer(4)):getInt32(1)
+                end)
+                return memory.toError(err).reason",
+            )
+            .eval::<String>()
+            .unwrap();
+        assert_eq!(reason, "OutOfBounds");
+    }
+
+    #[test]
+    fn shared_between_states() {
+        let lua = lua();
+        let buffer = lua
+            .load(
+                "buffer = memory.SharedArrayBuffer(16)
+                view = memory.Int32Array(buffer)
+                assert(view.buffer == view.buffer and view.buffer == buffer, 'same storage')
+                assert(buffer ~= memory.SharedArrayBuffer(16) and buffer ~= view, 'other storage')
+                return buffer",
+            )
+            .eval::<super::SharedArrayBuffer>()
+            .unwrap();
+        std::thread::spawn(move || {
+            let lua = self::lua();
+            lua.globals().set("buffer", buffer).unwrap();
+            lua.load("memory.Int32Array(buffer)[2] = -7")
+                .exec()
+                .unwrap();
+        })
+        .join()
+        .unwrap();
+        assert_eq!(lua.load("return view[2]").eval::<i32>().unwrap(), -7);
+        let same = lua
+            .load(
+                "local buffer = memory.ArrayBuffer(4)
+                local view = memory.UInt8Array(buffer)
+                return view.buffer == buffer, buffer == memory.ArrayBuffer(4)",
+            )
+            .eval::<(bool, bool)>()
+            .unwrap();
+        assert_eq!(same, (true, false));
+    }
+
+    #[test]
+    fn atomics() {
+        let lua = lua();
+        lua.load(
+            "local function check(got, expected, what)
+                assert(got == expected, what .. ': got ' .. tostring(got))
+            end
+            local buffer = memory.SharedArrayBuffer(24)
+            local i32 = memory.Int32Array(buffer, 0, 4)
+            local u64 = memory.UInt64Array(buffer, 16, 1)
+            local A = memory.Atomics
+            check(A.store(i32, 1, 2^31 + 5), -2147483643, 'store wraps')
+            check(A.load(i32, 1), -2147483643, 'load')
+            check(A.add(i32, 2, 10), 0, 'add')
+            check(A.sub(i32, 2, 3), 10, 'sub')
+            check(A.load(i32, 2), 7, 'load after sub')
+            check(A['and'](i32, 2, 6), 7, 'and')
+            check(A['or'](i32, 2, 8), 6, 'or')
+            check(A.xor(i32, 2, 1), 14, 'xor')
+            check(A.load(i32, 2), 15, 'load after xor')
+            check(A.exchange(i32, 2, -1), 15, 'exchange')
+            check(A.compareExchange(i32, 2, -1, 4), -1, 'compareExchange that swaps')
+            check(A.compareExchange(i32, 2, -1, 9), 4, 'compareExchange that fails')
+            check(A.load(i32, 2), 4, 'load after compareExchange')
+            check(i32[1], -2147483643, 'index after store')
+            check(A.store(u64, 1, -1), -1, '64-bit store')
+            check(A.add(u64, 1, 2), -1, '64-bit add')
+            check(A.load(u64, 1), 1, '64-bit add wraps')
+            check(A.store(i32, 3, 1e20), 1661992960, 'store of a huge float')
+            check(i32[3], 1661992960, 'index after a huge float')
+            memory.UInt8Array(buffer)[4] = 0
+            check(i32[2], 4, 'index after a byte write')
+            check(A.wait(i32, 4, 1, 0), 'not-equal', 'wait on another value')
+            check(A.wait(i32, 4, 0, 1), 'timed-out', 'wait with a timeout')
+            check(A.notify(i32, 4), 0, 'notify without waiters')
+            for what, call in pairs({
+                ['Float32Array'] = function() return A.load(memory.Float32Array(buffer), 1) end,
+                ['wait on an ArrayBuffer'] = function() return A.wait(memory.Int32Array(4), 1, 0, 0) end,
+            }) do
+                local _, err = pcall(call)
+                check(memory.toError(err).reason, 'KindMismatch', what)
+            end
+            local _, err = pcall(A.load, i32, 5)
+            check(memory.toError(err).reason, 'OutOfBounds', 'load past the end')",
+        )
+        .exec()
+        .unwrap();
+
+        let buffer = lua
+            .load("view = memory.Int32Array(memory.SharedArrayBuffer(8)) return view.buffer")
+            .eval::<super::SharedArrayBuffer>()
+            .unwrap();
+        let waiter = std::thread::spawn(move || {
+            let lua = self::lua();
+            lua.globals().set("buffer", buffer).unwrap();
+            lua.load(
+                "local view = memory.Int32Array(buffer)
+                local result = memory.Atomics.wait(view, 2, 0)
+                return result .. ' ' .. memory.Atomics.load(view, 1)",
+            )
+            .eval::<String>()
+            .unwrap()
+        });
+        lua.load("memory.Atomics.store(view, 1, 42)")
+            .exec()
+            .unwrap();
+        while lua
+            .load("return memory.Atomics.notify(view, 2, 1)")
+            .eval::<usize>()
+            .unwrap()
+            == 0
+        {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(waiter.join().unwrap(), "ok 42");
+    }
+
+    #[test]
+    fn bulk_operations() {
+        let lua = lua();
+        lua.load(
+            "local function check(array, expected, what)
+                local got = array:join(',')
+                assert(got == expected, what .. ': got ' .. got)
+            end
+            check(memory.Int8Array(5):fill(7), '7,7,7,7,7', 'fill')
+            check(memory.Int8Array(5):fill(300, 2, -2), '0,44,44,44,0', 'fill range')
+            check(memory.UInt8ClampedArray(3):fill(300), '255,255,255', 'fill clamped')
+            local array = memory.Int16Array.of(1, 2, 3, 4, 5, 6)
+            array:set(array:subarray(1, 4), 3)
+            check(array, '1,2,1,2,3,4', 'set from an overlapping view')
+            local floats = memory.Float32Array(4)
+            floats:set(memory.Int8Array.of(-1, 2), 2)
+            floats:set({ 0.5 })
+            check(floats, '0.5,-1,2,0', 'set from an array and a table')
+            check(memory.UInt8Array.of(1, 2, 3, 4):copyWithin(5, 1), '1,2,3,4', 'copyWithin past the end')
+            check(memory.UInt8Array.of(1, 2, 3, 4):copyWithin(2, 1), '1,1,2,3', 'copyWithin forwards')
+            check(memory.UInt8Array.of(1, 2, 3, 4):copyWithin(1, 3), '3,4,3,4', 'copyWithin backwards')
+            check(memory.UInt8Array.of(1, 2, 3, 4, 5):copyWithin(-2, 1, 3), '1,2,3,1,2', 'copyWithin relative')
+            local _, err = pcall(array.set, array, { 1, 2 }, 6)
+            assert(memory.toError(err).reason == 'OutOfBounds', 'set past the end')",
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let lua = lua();
+        let check = |policy: &str, expected: [&str; 11]| {
+            lua.globals().set("policy", policy).unwrap();
+            lua.globals().set("expected", expected).unwrap();
+            lua.load(
+                "local i8 = memory.Int8Array(1)
+                local u64 = memory.UInt64Array(1)
+                local view = memory.DataView(memory.ArrayBuffer(8))
+                local shared = memory.Int32Array(memory.SharedArrayBuffer(4))
+                local i128 = memory.Int128Array(1)
+                local u128 = memory.UInt128Array(1)
+                for _, target in ipairs({ i8, u64, view, shared, i128, u128 }) do
+                    target.overflow = policy
+                    assert(target.overflow == policy, 'overflow field')
+                end
+                for i, case in ipairs({
+                    { 'Int8Array 200', function() i8[1] = 200 return i8[1] end },
+                    { 'Int8Array -1e300', function() i8[1] = -1e300 return i8[1] end },
+                    { 'UInt64Array -1', function() u64[1] = -1 return u64[1] end },
+                    { 'UInt64Array 2^64', function() u64[1] = 2^64 return u64[1] end },
+                    { 'setUInt16', function() view:setUInt16(0, 70000) return view:getUInt16(0) end },
+                    { 'setInt64', function() view:setInt64(0, 2^63) return view:getInt64(0) end },
+                    { 'Atomics.store', function() return memory.Atomics.store(shared, 1, 2^31) end },
+                    { 'Int128Array 1e40', function() i128[1] = 1e40 return i128[1] end },
+                    { 'Int128Array 2^127', function() i128[1] = 2^127 return i128[1] end },
+                    { 'UInt128Array 1e40', function() u128[1] = 1e40 return u128[1] end },
+                    { 'UInt128Array -1e40', function() u128[1] = -1e40 return u128[1] end },
+                }) do
+                    local ok, value = pcall(case[2])
+                    local got = ok and tostring(value) or memory.toError(value).reason
+                    assert(got == expected[i], policy .. ' ' .. case[1] .. ': got ' .. got)
+                end",
+            )
+            .exec()
+            .unwrap();
+        };
+        check(
+            "wrap",
+            [
+                "-56",
+                "0",
+                "-1",
+                "0",
+                "4464",
+                "-9223372036854775808",
+                "-2147483648",
+                "131811359292784863348164811482388758528",
+                "-170141183460469231731687303715884105728",
+                "131811359292784863348164811482388758528",
+                "208471007628153600115209795949379452928",
+            ],
+        );
+        check(
+            "clamp",
+            [
+                "127",
+                "-128",
+                "0",
+                "-1",
+                "65535",
+                "9223372036854775807",
+                "2147483647",
+                "170141183460469231731687303715884105727",
+                "170141183460469231731687303715884105727",
+                "340282366920938463463374607431768211455",
+                "0",
+            ],
+        );
+        check("error", ["Overflow"; 11]);
+    }
+
+    #[test]
+    fn wide_integers() {
+        let lua = lua();
+        lua.globals()
+            .set(
+                "expected",
+                [
+                    u128::MAX.to_string(),
+                    (u128::MAX - 1).to_string(),
+                    (1u128 << 100).to_string(),
+                    i128::MAX.to_string(),
+                ],
+            )
+            .unwrap();
+        lua.load(
+            "local I, U = memory.Int128, memory.UInt128
+            local MAX, MAX_MINUS_ONE, TWO_TO_100, I128_MAX = table.unpack(expected)
+            local function check(got, expected, what)
+                got = tostring(got)
+                assert(got == expected, what .. ': got ' .. got)
+            end
+            local max = U('0xffffffffffffffffffffffffffffffff')
+            check(max, MAX, 'parse hexadecimal')
+            check(max + 1, '0', 'unsigned addition wraps')
+            check(U(5) - 7, MAX_MINUS_ONE, 'unsigned subtraction wraps')
+            check(I(-7) // 2, '-4', 'floor division')
+            check(I(-7) % 2, '1', 'modulo')
+            check(U(7) // 2, '3', 'unsigned division')
+            check(I(3) * I('-4'), '-12', 'multiplication')
+            check(I(1) << 100, TWO_TO_100, 'shift')
+            check(-I(5), '-5', 'negation')
+            check(I(10) / 4, '2.5', 'float division')
+            check(I(1, 2), '18446744073709551618', 'from halves')
+            check(I(1, 2).hi, '1', 'hi')
+            check(I(1, 2).lo, '2', 'lo')
+            assert(~U(0) == max, 'bitwise not')
+            assert(I(-1) < I(0), 'signed comparison')
+            assert(U(1) < max, 'unsigned comparison')
+            assert(I(2) <= 2, 'comparison with a number')
+            assert(not (max < U(1)), 'max is not below one')
+            local clamped = memory.UInt8ClampedArray(3)
+            clamped[1], clamped[2], clamped[3] = max, I(-5), U(200)
+            check(clamped:join(','), '255,0,200', 'clamped conversion')
+            local unsigned = memory.UInt128Array(1)
+            unsigned[1] = max
+            check(unsigned[1], MAX, 'UInt128Array keeps max')
+            local signed = memory.Int128Array(1)
+            signed.overflow = 'clamp'
+            signed[1] = max
+            check(signed[1], I128_MAX, 'Int128Array clamps max')
+            unsigned.overflow = 'error'
+            assert(not pcall(function() unsigned[1] = I(-1) end), 'negative into UInt128Array')
+            unsigned.overflow = 'clamp'
+            unsigned[1] = 2^200
+            check(unsigned[1], MAX, 'clamp a huge number')
+            unsigned[1] = I(-3)
+            check(unsigned[1], '0', 'clamp a negative value')",
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn typed_array_sources() {
+        let lua = lua();
+        lua.load(
+            "local function check(array, expected, what)
+                local got = array.name .. ':' .. array:join(',')
+                assert(got == expected, what .. ': got ' .. got)
+            end
+            check(memory.Float32Array({ 1, 2.5, -3 }), 'Float32Array:1,2.5,-3', 'table')
+            check(memory.UInt8Array('3a'), 'UInt8Array:51,97', 'string')
+            check(memory.Int16Array(memory.Float64Array.of(1.9, -70000)), 'Int16Array:1,-4464',
+                'typed array')
+            check(memory.Int8Array(ipairs({ 4, 5, 6 })), 'Int8Array:4,5,6', 'ipairs')
+            check(memory.UInt16Array(memory.Int8Array.of(7, 8):values()), 'UInt16Array:7,8',
+                'values iterator')
+            check(memory.Int32Array.of(), 'Int32Array:', 'empty of')
+            check(memory.Int32Array.of(1, 2, 3), 'Int32Array:1,2,3', 'of')
+            check(memory.UInt8Array.from({ 1, 2 }), 'UInt8Array:1,2', 'from a table')
+            check(memory.UInt8Array.from('ab', function(v, i) return v + i end), 'UInt8Array:98,100',
+                'from with a map function')
+            check(memory.Float64Array.from(memory.Int8Array.of(-1, 1)), 'Float64Array:-1,1',
+                'from a typed array')
+            check(memory.Int8Array.from(memory.Int8Array.of(3, 4):keys()), 'Int8Array:1,2',
+                'from a keys iterator')
+            assert(type(memory.Int8Array) == 'table', 'constructors are tables')
+            assert(memory.Int64Array.BYTES_PER_ELEMENT == 8, 'BYTES_PER_ELEMENT')
+            local _, err = pcall(memory.Int8Array, { 1, 'x' })
+            assert(err.kind == 'TypeError' and err.argument == 1, 'non-numeric table element')",
+        )
+        .exec()
+        .unwrap();
+    }
+}
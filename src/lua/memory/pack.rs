@@ -0,0 +1,647 @@
+use super::error::Overloads;
+use super::{ArrayBufferLike, Error, ErrorKind, Int128, UInt128};
+
+/// Largest alignment selected by a bare `!`.
+const NATIVE_ALIGNMENT: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// An integer of 1 to 16 bytes.
+    Int {
+        size: usize,
+        signed: bool,
+    },
+    Float,
+    Double,
+    /// A string of exactly this many bytes, padded with zeros when packing.
+    Fixed(usize),
+    /// A string preceded by its length, as an unsigned integer of this many
+    /// bytes.
+    Prefixed(usize),
+    /// A string followed by a zero byte.
+    Terminated,
+    /// A single zero byte.
+    Padding,
+    /// No data, only the alignment of the item.
+    Align,
+}
+
+impl Field {
+    /// Size the alignment of the field derives from, as in `string.pack`.
+    fn natural_alignment(self) -> usize {
+        match self {
+            Field::Int { size, .. } | Field::Prefixed(size) => size,
+            Field::Float => 4,
+            Field::Double => 8,
+            Field::Fixed(_) | Field::Terminated | Field::Padding | Field::Align => 1,
+        }
+    }
+
+    /// Whether the field consumes one of the values passed to `pack` and
+    /// produces one from `unpack`.
+    fn has_value(self) -> bool {
+        !matches!(self, Field::Padding | Field::Align)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Item {
+    field: Field,
+    little_endian: bool,
+    /// A power of two, relative to the start of the record.
+    alignment: usize,
+}
+
+/// A compiled `string.pack` format string, reading and writing records
+/// straight from and into buffers instead of strings.
+///
+/// Supports the options of `string.pack`: `<`, `>` and `=` select the byte
+/// order, `![n]` the maximum alignment, `b`, `B`, `h`, `H`, `i[n]`, `I[n]`,
+/// `l`, `L`, `j`, `J` and `T` integers, `f`, `d` and `n` floats, `cn`, `s[n]`
+/// and `z` strings, `x` a padding byte and `Xop` the alignment of `op`. Spaces
+/// are ignored.
+///
+/// Integers of 9 to 16 bytes also accept `Int128` and `UInt128` values, and
+/// unpack to them instead of Lua integers.
+#[derive(Debug, Clone)]
+pub struct PackFormat {
+    _items: Vec<Item>,
+}
+
+/// Reads the decimal count following an option, if any.
+fn count(format: &[u8], index: &mut usize) -> Option<usize> {
+    let start = *index;
+    while *index < format.len() && format[*index].is_ascii_digit() {
+        *index += 1;
+    }
+    std::str::from_utf8(&format[start..*index])
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Parses the option at `format[*index - 1]`, or returns `None` if it is not
+/// a field.
+fn field(option: u8, format: &[u8], index: &mut usize) -> Option<Field> {
+    let mut size = |default: usize| match count(format, index) {
+        None => Some(default),
+        Some(size) if (1..=16).contains(&size) => Some(size),
+        Some(_) => None,
+    };
+    Some(match option {
+        b'b' | b'B' => Field::Int {
+            size: 1,
+            signed: option == b'b',
+        },
+        b'h' | b'H' => Field::Int {
+            size: 2,
+            signed: option == b'h',
+        },
+        b'i' | b'I' => Field::Int {
+            size: size(4)?,
+            signed: option == b'i',
+        },
+        b'l' | b'L' | b'j' | b'J' | b'T' => Field::Int {
+            size: 8,
+            signed: matches!(option, b'l' | b'j'),
+        },
+        b'f' => Field::Float,
+        b'd' | b'n' => Field::Double,
+        b'c' => Field::Fixed(count(format, index)?),
+        b's' => Field::Prefixed(size(8)?),
+        b'z' => Field::Terminated,
+        b'x' => Field::Padding,
+        _ => return None,
+    })
+}
+
+/// Error for a value that `pack` cannot store, worded like the errors of
+/// `string.pack`.
+fn bad_argument(position: usize, kind: ErrorKind, message: String) -> Error {
+    Error::Argument {
+        kind,
+        argument: Some(position),
+        message: format!("bad argument #{} to 'pack' ({})", position, message),
+    }
+}
+
+/// An integer to pack, as the two's complement bits of its value.
+struct Integer {
+    bits: u128,
+    negative: bool,
+    /// Size in bytes of the type the value comes from; narrower fields are
+    /// range checked, like `string.pack` does for Lua integers.
+    width: usize,
+}
+
+impl Integer {
+    fn new(value: i128, width: usize) -> Self {
+        Integer {
+            bits: value as u128,
+            negative: value < 0,
+            width,
+        }
+    }
+
+    /// Whether the value is representable in `size` bytes.
+    fn fits(&self, size: usize, signed: bool) -> bool {
+        if size >= self.width {
+            true
+        } else if signed {
+            let limit = 1u128 << (size * 8 - 1);
+            if self.negative {
+                self.bits.wrapping_neg() <= limit
+            } else {
+                self.bits < limit
+            }
+        } else {
+            !self.negative && self.bits < 1u128 << (size * 8)
+        }
+    }
+
+    /// The bits to store; values are sign-extended into wider signed fields
+    /// and zero-extended into wider unsigned ones, like `string.pack` does.
+    fn extended(&self, signed: bool) -> u128 {
+        if signed || self.width == 16 {
+            self.bits
+        } else {
+            self.bits & (u128::MAX >> (128 - self.width * 8))
+        }
+    }
+}
+
+fn integer(value: &mlua::Value, position: usize) -> Result<Integer, Error> {
+    let wide = |err: mlua::Error| bad_argument(position, ErrorKind::Type, err.to_string());
+    match value {
+        mlua::Value::Integer(value) => Ok(Integer::new((*value).into(), 8)),
+        mlua::Value::Number(value)
+            if value.fract() == 0.0 && *value >= -(2f64.powi(63)) && *value < 2f64.powi(63) =>
+        {
+            Ok(Integer::new(*value as i128, 8))
+        }
+        mlua::Value::UserData(ud) if ud.is::<Int128>() => {
+            Ok(Integer::new(ud.borrow::<Int128>().map_err(wide)?.0, 16))
+        }
+        mlua::Value::UserData(ud) if ud.is::<UInt128>() => Ok(Integer {
+            bits: ud.borrow::<UInt128>().map_err(wide)?.0,
+            negative: false,
+            width: 16,
+        }),
+        mlua::Value::Number(_) => Err(bad_argument(
+            position,
+            ErrorKind::Range,
+            "number has no integer representation".into(),
+        )),
+        value => Err(bad_argument(
+            position,
+            ErrorKind::Type,
+            format!("number expected, got {}", value.type_name()),
+        )),
+    }
+}
+
+fn number(value: &mlua::Value, position: usize) -> Result<f64, Error> {
+    match value {
+        mlua::Value::Integer(value) => Ok(*value as f64),
+        mlua::Value::Number(value) => Ok(*value),
+        value => Err(bad_argument(
+            position,
+            ErrorKind::Type,
+            format!("number expected, got {}", value.type_name()),
+        )),
+    }
+}
+
+fn string<'a>(value: &'a mlua::Value, position: usize) -> Result<&'a [u8], Error> {
+    match value {
+        mlua::Value::String(value) => Ok(value.as_bytes()),
+        value => Err(bad_argument(
+            position,
+            ErrorKind::Type,
+            format!("string expected, got {}", value.type_name()),
+        )),
+    }
+}
+
+/// Appends the `size` low bytes of `value` in the requested byte order.
+fn push_int(out: &mut Vec<u8>, value: u128, size: usize, little_endian: bool) {
+    if little_endian {
+        out.extend_from_slice(&value.to_le_bytes()[..size]);
+    } else {
+        out.extend_from_slice(&value.to_be_bytes()[16 - size..]);
+    }
+}
+
+/// Reads an integer of `bytes.len()` bytes, sign-extending it if `signed`.
+fn read_int(bytes: &[u8], signed: bool, little_endian: bool) -> u128 {
+    let mut value = [0; 16];
+    if little_endian {
+        value[..bytes.len()].copy_from_slice(bytes);
+    } else {
+        value[16 - bytes.len()..].copy_from_slice(bytes);
+    }
+    let value = if little_endian {
+        u128::from_le_bytes(value)
+    } else {
+        u128::from_be_bytes(value)
+    };
+    let shift = 128 - 8 * bytes.len() as u32;
+    if signed {
+        (((value << shift) as i128) >> shift) as u128
+    } else {
+        value
+    }
+}
+
+/// Sequential reads from a buffer, failing past its end.
+struct Reader<'a> {
+    buffer: &'a ArrayBufferLike,
+    start: usize,
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        let offset = self.start + self.position;
+        let out_of_bounds = Error::OutOfBounds {
+            target: "ArrayBuffer",
+            offset: offset.saturating_add(len),
+            len: self.buffer.len(),
+        };
+        // Checked first so that a corrupt length prefix cannot trigger a huge
+        // allocation.
+        if len > self.buffer.len().saturating_sub(offset) {
+            return Err(out_of_bounds);
+        }
+        let mut bytes = vec![0; len];
+        self.buffer
+            .read_into(offset, &mut bytes)
+            .ok_or(out_of_bounds)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    /// Reads up to the next zero byte, which is consumed but not returned.
+    fn take_terminated(&mut self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        loop {
+            let offset = self.start + self.position;
+            let available = self.buffer.len().saturating_sub(offset).min(64);
+            if available == 0 {
+                return Err(Error::OutOfBounds {
+                    target: "ArrayBuffer",
+                    offset,
+                    len: self.buffer.len(),
+                });
+            }
+            let chunk = self.take(available)?;
+            if let Some(end) = chunk.iter().position(|byte| *byte == 0) {
+                bytes.extend_from_slice(&chunk[..end]);
+                self.position -= available - end - 1;
+                return Ok(bytes);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+    }
+}
+
+impl PackFormat {
+    /// Compiles `format`, failing with a [`Error::Syntax`] pointing at the
+    /// first invalid option.
+    pub fn parse(format: &str) -> Result<Self, Error> {
+        let format = format.as_bytes();
+        let mut items = Vec::new();
+        let mut little_endian = cfg!(target_endian = "little");
+        let mut max_alignment = 1;
+        let mut index = 0;
+        while index < format.len() {
+            let start = index;
+            let invalid = || Error::Syntax {
+                format: "pack format",
+                offset: start,
+            };
+            let option = format[index];
+            index += 1;
+            let (field, alignment) = match option {
+                b' ' => continue,
+                b'<' => {
+                    little_endian = true;
+                    continue;
+                }
+                b'>' => {
+                    little_endian = false;
+                    continue;
+                }
+                b'=' => {
+                    little_endian = cfg!(target_endian = "little");
+                    continue;
+                }
+                b'!' => {
+                    max_alignment = match count(format, &mut index) {
+                        None => NATIVE_ALIGNMENT,
+                        Some(n) if (1..=16).contains(&n) => n,
+                        Some(_) => return Err(invalid()),
+                    };
+                    continue;
+                }
+                b'X' => {
+                    let next = format.get(index).copied().ok_or_else(invalid)?;
+                    index += 1;
+                    match field(next, format, &mut index) {
+                        Some(Field::Fixed(_) | Field::Terminated) | None => return Err(invalid()),
+                        Some(next) => (Field::Align, next.natural_alignment()),
+                    }
+                }
+                option => {
+                    let field = field(option, format, &mut index).ok_or_else(invalid)?;
+                    (field, field.natural_alignment())
+                }
+            };
+            let alignment = alignment.min(max_alignment);
+            if !alignment.is_power_of_two() {
+                return Err(invalid());
+            }
+            items.push(Item {
+                field,
+                little_endian,
+                alignment,
+            });
+        }
+        Ok(PackFormat { _items: items })
+    }
+
+    /// Size of every record, or `None` if the format has variable-length
+    /// strings.
+    pub fn size(&self) -> Option<usize> {
+        let mut size = 0usize;
+        for item in &self._items {
+            size = size.next_multiple_of(item.alignment);
+            size += match item.field {
+                Field::Int { size, .. } => size,
+                Field::Float => 4,
+                Field::Double => 8,
+                Field::Fixed(len) => len,
+                Field::Padding => 1,
+                Field::Align => 0,
+                Field::Prefixed(_) | Field::Terminated => return None,
+            };
+        }
+        Some(size)
+    }
+
+    /// Encodes `values` into a record. `first_argument` is the position of
+    /// the first value among the arguments of `pack`, used to word errors.
+    pub fn pack(&self, values: &[mlua::Value], first_argument: usize) -> Result<Vec<u8>, Error> {
+        let mut out = Vec::new();
+        let mut consumed = 0;
+        for item in &self._items {
+            out.resize(out.len().next_multiple_of(item.alignment), 0);
+            let position = first_argument + consumed;
+            let value = if item.field.has_value() {
+                consumed += 1;
+                values
+                    .get(consumed - 1)
+                    .ok_or_else(|| bad_argument(position, ErrorKind::Type, "no value".into()))?
+            } else {
+                &mlua::Value::Nil
+            };
+            match item.field {
+                Field::Int { size, signed } => {
+                    let value = integer(value, position)?;
+                    if !value.fits(size, signed) {
+                        return Err(bad_argument(
+                            position,
+                            ErrorKind::Range,
+                            "integer overflow".into(),
+                        ));
+                    }
+                    push_int(&mut out, value.extended(signed), size, item.little_endian);
+                }
+                Field::Float => {
+                    let bits = (number(value, position)? as f32).to_bits();
+                    push_int(&mut out, u128::from(bits), 4, item.little_endian);
+                }
+                Field::Double => {
+                    let bits = number(value, position)?.to_bits();
+                    push_int(&mut out, u128::from(bits), 8, item.little_endian);
+                }
+                Field::Fixed(len) => {
+                    let s = string(value, position)?;
+                    if s.len() > len {
+                        return Err(bad_argument(
+                            position,
+                            ErrorKind::Range,
+                            "string longer than given size".into(),
+                        ));
+                    }
+                    out.extend_from_slice(s);
+                    out.resize(out.len() + len - s.len(), 0);
+                }
+                Field::Prefixed(size) => {
+                    let s = string(value, position)?;
+                    if size < 8 && s.len() >> (size * 8) != 0 {
+                        return Err(bad_argument(
+                            position,
+                            ErrorKind::Range,
+                            "string length does not fit in given size".into(),
+                        ));
+                    }
+                    push_int(&mut out, s.len() as u128, size, item.little_endian);
+                    out.extend_from_slice(s);
+                }
+                Field::Terminated => {
+                    let s = string(value, position)?;
+                    if s.contains(&0) {
+                        return Err(bad_argument(
+                            position,
+                            ErrorKind::Range,
+                            "string contains zeros".into(),
+                        ));
+                    }
+                    out.extend_from_slice(s);
+                    out.push(0);
+                }
+                Field::Padding => out.push(0),
+                Field::Align => {}
+            }
+        }
+        Ok(out)
+    }
+
+    /// Packs `values` into `buffer` at the byte `offset`, returning the number
+    /// of bytes written. Nothing is written if any value is invalid or the
+    /// record does not fit.
+    pub fn pack_into(
+        &self,
+        buffer: &ArrayBufferLike,
+        offset: usize,
+        values: &[mlua::Value],
+        first_argument: usize,
+    ) -> Result<usize, Error> {
+        buffer.check_writable()?;
+        let bytes = self.pack(values, first_argument)?;
+        buffer
+            .write_from(offset, &bytes)
+            .ok_or(Error::OutOfBounds {
+                target: "ArrayBuffer",
+                offset: offset.saturating_add(bytes.len()),
+                len: buffer.len(),
+            })?;
+        Ok(bytes.len())
+    }
+
+    /// Decodes the record at the byte `offset` of `buffer`, returning its
+    /// values and the number of bytes it spans.
+    pub fn unpack<'lua>(
+        &self,
+        lua: &'lua mlua::Lua,
+        buffer: &ArrayBufferLike,
+        offset: usize,
+    ) -> mlua::Result<(Vec<mlua::Value<'lua>>, usize)> {
+        buffer.check_attached()?;
+        let mut reader = Reader {
+            buffer,
+            start: offset,
+            position: 0,
+        };
+        let mut values = Vec::new();
+        for item in &self._items {
+            let padding = reader.position.next_multiple_of(item.alignment) - reader.position;
+            reader.take(padding)?;
+            let little_endian = item.little_endian;
+            values.push(match item.field {
+                Field::Int { size, signed } => {
+                    let bits = read_int(&reader.take(size)?, signed, little_endian);
+                    if size <= 8 {
+                        mlua::Value::Integer(bits as mlua::Integer)
+                    } else if signed {
+                        mlua::Value::UserData(lua.create_userdata(Int128(bits as i128))?)
+                    } else {
+                        mlua::Value::UserData(lua.create_userdata(UInt128(bits))?)
+                    }
+                }
+                Field::Float => {
+                    let bits = read_int(&reader.take(4)?, false, little_endian) as u32;
+                    mlua::Value::Number(f64::from(f32::from_bits(bits)))
+                }
+                Field::Double => {
+                    let bits = read_int(&reader.take(8)?, false, little_endian) as u64;
+                    mlua::Value::Number(f64::from_bits(bits))
+                }
+                Field::Fixed(len) => mlua::Value::String(lua.create_string(&reader.take(len)?)?),
+                Field::Prefixed(size) => {
+                    let len = read_int(&reader.take(size)?, false, little_endian);
+                    let len = usize::try_from(len).unwrap_or(usize::MAX);
+                    mlua::Value::String(lua.create_string(&reader.take(len)?)?)
+                }
+                Field::Terminated => {
+                    mlua::Value::String(lua.create_string(&reader.take_terminated()?)?)
+                }
+                Field::Padding => {
+                    reader.take(1)?;
+                    continue;
+                }
+                Field::Align => continue,
+            });
+        }
+        Ok((values, reader.position))
+    }
+}
+
+impl mlua::UserData for PackFormat {
+    fn add_fields<'lua, F: mlua::UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("size", |_, this| Ok(this.size()));
+    }
+
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method(
+            "pack",
+            |_, this, args: (ArrayBufferLike, mlua::Value, mlua::MultiValue)| {
+                let (buffer, offset, values) = args;
+                let overloads = Overloads {
+                    name: "pack",
+                    signatures: &["(buffer [, offset [, ...]])"],
+                };
+                let offset = overloads.index(Some(&offset), 2, "offset")?;
+                Ok(this.pack_into(&buffer, offset.unwrap_or(0), &values.into_vec(), 3)?)
+            },
+        );
+        methods.add_method(
+            "unpack",
+            |lua, this, args: (ArrayBufferLike, mlua::Value)| {
+                let (buffer, offset) = args;
+                let overloads = Overloads {
+                    name: "unpack",
+                    signatures: &["(buffer [, offset])"],
+                };
+                let offset = overloads.index(Some(&offset), 2, "offset")?;
+                let (mut values, len) = this.unpack(lua, &buffer, offset.unwrap_or(0))?;
+                values.push(mlua::Value::Integer(len as mlua::Integer));
+                Ok(mlua::MultiValue::from_vec(values))
+            },
+        );
+    }
+}
+
+/// Reads a format string, compiling it, or a format compiled with
+/// `memory.struct.compile`.
+fn to_format(value: mlua::Value) -> mlua::Result<PackFormat> {
+    match &value {
+        mlua::Value::String(format) => Ok(PackFormat::parse(format.to_str()?)?),
+        mlua::Value::UserData(ud) if ud.is::<PackFormat>() => {
+            Ok(ud.borrow::<PackFormat>()?.clone())
+        }
+        _ => Err(mlua::Error::FromLuaConversionError {
+            from: value.type_name(),
+            to: "PackFormat",
+            message: Some("expected a format string or a compiled format".into()),
+        }),
+    }
+}
+
+/// Creates the `memory.struct` table, whose functions take either a format
+/// string or a format compiled with `compile`.
+pub fn create_table<'lua>(lua: &'lua mlua::Lua) -> mlua::Result<mlua::Table<'lua>> {
+    let struct_table = lua.create_table()?;
+
+    struct_table.raw_set(
+        "compile",
+        lua.create_function(|_, format: String| Ok(PackFormat::parse(&format)?))?,
+    )?;
+    struct_table.raw_set(
+        "size",
+        lua.create_function(|_, format: mlua::Value| Ok(to_format(format)?.size()))?,
+    )?;
+    struct_table.raw_set(
+        "pack",
+        lua.create_function(
+            |_, args: (mlua::Value, ArrayBufferLike, mlua::Value, mlua::MultiValue)| {
+                let (format, buffer, offset, values) = args;
+                let overloads = Overloads {
+                    name: "pack",
+                    signatures: &["(format, buffer [, offset [, ...]])"],
+                };
+                let offset = overloads.index(Some(&offset), 3, "offset")?;
+                let format = to_format(format)?;
+                Ok(format.pack_into(&buffer, offset.unwrap_or(0), &values.into_vec(), 4)?)
+            },
+        )?,
+    )?;
+    struct_table.raw_set(
+        "unpack",
+        lua.create_function(|lua, args: (mlua::Value, ArrayBufferLike, mlua::Value)| {
+            let (format, buffer, offset) = args;
+            let overloads = Overloads {
+                name: "unpack",
+                signatures: &["(format, buffer [, offset])"],
+            };
+            let offset = overloads.index(Some(&offset), 3, "offset")?;
+            let format = to_format(format)?;
+            let (mut values, len) = format.unpack(lua, &buffer, offset.unwrap_or(0))?;
+            values.push(mlua::Value::Integer(len as mlua::Integer));
+            Ok(mlua::MultiValue::from_vec(values))
+        })?,
+    )?;
+
+    Ok(struct_table)
+}
@@ -0,0 +1,221 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const WORD: usize = core::mem::size_of::<u64>();
+
+/// Atomically reference counted byte storage that can be handed to other
+/// threads, and therefore to other Lua states.
+///
+/// The bytes are stored in 64-bit atomic words and every access, plain or
+/// through `Atomics`, goes through those words, so the memory is never
+/// accessed with atomics of mixed sizes. Plain reads and writes use relaxed
+/// operations: concurrent readers and writers never race in the Rust sense,
+/// but ordering between states has to be established through `Atomics`.
+/// Writes covering only part of a word replace the bytes they cover with a
+/// compare-and-swap loop, leaving the rest of the word untouched.
+///
+/// To share a buffer, the host takes it out of one state (e.g. with
+/// `globals().get::<_, SharedArrayBuffer>()`), moves the clone to the thread
+/// running the other state and stores it there; both states then view the
+/// same bytes.
+#[derive(Debug, Clone)]
+pub struct SharedArrayBuffer {
+    _words: std::sync::Arc<[AtomicU64]>,
+    _len: usize,
+}
+
+/// An aligned element of 1, 2, 4 or 8 bytes within one of the words of a
+/// [`SharedArrayBuffer`].
+///
+/// Values are the element's bits in the low bits of a `u64`.
+#[derive(Debug, Clone, Copy)]
+pub struct AtomicElement<'a> {
+    word: &'a AtomicU64,
+    /// The bits of the word holding the element.
+    mask: u64,
+}
+
+impl AtomicElement<'_> {
+    fn shift(&self) -> u32 {
+        self.mask.trailing_zeros()
+    }
+
+    pub fn load(&self, order: Ordering) -> u64 {
+        (self.word.load(order) & self.mask) >> self.shift()
+    }
+
+    pub fn store(&self, bits: u64, order: Ordering) {
+        if self.mask == u64::MAX {
+            self.word.store(bits, order);
+        } else {
+            self.update(|_| bits, order);
+        }
+    }
+
+    /// Atomically replaces the element with `f(old)` and returns `old`.
+    pub fn update(&self, f: impl Fn(u64) -> u64, order: Ordering) -> u64 {
+        let shift = self.shift();
+        let old = self
+            .word
+            .fetch_update(order, Ordering::Relaxed, |word| {
+                let new = f((word & self.mask) >> shift) << shift;
+                Some(word & !self.mask | new & self.mask)
+            })
+            .unwrap_or_else(|word| word);
+        (old & self.mask) >> shift
+    }
+
+    /// Identifies the element across every handle to the buffer.
+    pub fn address(&self) -> usize {
+        self.word as *const AtomicU64 as usize + self.shift() as usize / 8
+    }
+}
+
+impl SharedArrayBuffer {
+    pub fn new(size: usize) -> Result<Self, super::Error> {
+        let mut v = Vec::new();
+        v.try_reserve_exact(size.div_ceil(WORD))
+            .map_err(|_| super::Error::Allocation)?;
+        v.resize_with(size.div_ceil(WORD), || AtomicU64::new(0));
+        Ok(Self {
+            _words: v.into(),
+            _len: size,
+        })
+    }
+
+    /// Calls `f` with the index of each word overlapping the `len` bytes at
+    /// `offset`, the range of the word they cover and where that range starts
+    /// within those bytes.
+    fn for_each_word(
+        &self,
+        offset: usize,
+        len: usize,
+        mut f: impl FnMut(&AtomicU64, std::ops::Range<usize>, usize),
+    ) -> Option<()> {
+        let end = offset.checked_add(len)?;
+        if end > self._len {
+            return None;
+        }
+        let mut position = offset;
+        while position < end {
+            let start = position % WORD;
+            let stop = WORD.min(start + end - position);
+            f(
+                &self._words[position / WORD],
+                start..stop,
+                position - offset,
+            );
+            position += stop - start;
+        }
+        Some(())
+    }
+
+    /// Copies `dest.len()` bytes starting at `offset` into `dest`.
+    ///
+    /// Returns `None` without touching `dest` if the range is out of bounds.
+    pub fn read_into(&self, offset: usize, dest: &mut [u8]) -> Option<()> {
+        self.for_each_word(offset, dest.len(), |word, range, at| {
+            let bytes = word.load(Ordering::Relaxed).to_ne_bytes();
+            dest[at..at + range.len()].copy_from_slice(&bytes[range]);
+        })
+    }
+
+    /// Copies `src` into the buffer starting at `offset`.
+    ///
+    /// Returns `None` without writing anything if the range is out of bounds.
+    pub fn write_from(&self, offset: usize, src: &[u8]) -> Option<()> {
+        self.for_each_word(offset, src.len(), |word, range, at| {
+            let mut bytes = [0; WORD];
+            bytes[range.clone()].copy_from_slice(&src[at..at + range.len()]);
+            if range.len() == WORD {
+                word.store(u64::from_ne_bytes(bytes), Ordering::Relaxed);
+            } else {
+                let mut mask = [0; WORD];
+                mask[range].fill(0xff);
+                let mask = u64::from_ne_bytes(mask);
+                let bits = u64::from_ne_bytes(bytes);
+                let _ = word.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old| {
+                    Some(old & !mask | bits)
+                });
+            }
+        })
+    }
+
+    /// Returns the `size` bytes at `offset` as an atomic element, if they are
+    /// in bounds and aligned to `size`, which must be 1, 2, 4 or 8.
+    pub fn atomic(&self, offset: usize, size: usize) -> Option<AtomicElement<'_>> {
+        debug_assert!(size.is_power_of_two() && size <= WORD);
+        if !offset.is_multiple_of(size) || offset.checked_add(size)? > self._len {
+            return None;
+        }
+        let mut mask = [0; WORD];
+        mask[offset % WORD..offset % WORD + size].fill(0xff);
+        Some(AtomicElement {
+            word: &self._words[offset / WORD],
+            mask: u64::from_ne_bytes(mask),
+        })
+    }
+
+    /// Whether both handles refer to the same storage.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        std::sync::Arc::ptr_eq(&self._words, &other._words)
+    }
+
+    pub fn len(&self) -> usize {
+        self._len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self._len == 0
+    }
+}
+
+impl Default for SharedArrayBuffer {
+    fn default() -> Self {
+        Self {
+            _words: Vec::new().into(),
+            _len: 0,
+        }
+    }
+}
+
+impl std::fmt::Display for SharedArrayBuffer {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.write_str("SharedArrayBuffer { ")?;
+        let mut bytes = vec![0; self._len];
+        self.read_into(0, &mut bytes);
+        for v in bytes {
+            write!(fmt, "{}, ", v)?;
+        }
+        fmt.write_str(" }")
+    }
+}
+
+impl mlua::UserData for SharedArrayBuffer {
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_function(
+            mlua::MetaMethod::ToString,
+            |_, this: SharedArrayBuffer| -> Result<String, _> { Ok(format!("{this}")) },
+        );
+        methods.add_meta_function(
+            mlua::MetaMethod::Len,
+            |_, this: SharedArrayBuffer| -> Result<usize, _> { Ok(this.len()) },
+        );
+        methods.add_meta_function(
+            mlua::MetaMethod::Eq,
+            |_, (lhs, rhs): (mlua::AnyUserData, mlua::AnyUserData)| {
+                Ok(match (lhs.borrow::<Self>(), rhs.borrow::<Self>()) {
+                    (Ok(lhs), Ok(rhs)) => lhs.ptr_eq(&rhs),
+                    _ => false,
+                })
+            },
+        );
+        methods.add_method(
+            "toString",
+            |lua, this, args: (Option<usize>, Option<usize>)| {
+                let (offset, length) = args;
+                let range = super::byte_range("SharedArrayBuffer", this.len(), offset, length)?;
+                super::ArrayBufferLike::from(this.clone()).to_lua_string(lua, range)
+            },
+        );
+    }
+}
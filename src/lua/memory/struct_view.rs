@@ -0,0 +1,424 @@
+use std::rc::Rc;
+
+use super::typed_array::{Numeric, TypedArrayKind, TypedArrayVariant};
+use super::{ArrayBufferLike, Error, ErrorKind, TypedArray};
+
+/// Looks up the element type of a field by its Rust name, e.g. `f32`.
+fn kind_from_name(name: &str) -> Option<TypedArrayKind> {
+    Some(match name {
+        "i8" => TypedArrayKind::SInt8,
+        "u8" => TypedArrayKind::UInt8,
+        "i16" => TypedArrayKind::SInt16,
+        "u16" => TypedArrayKind::UInt16,
+        "i32" => TypedArrayKind::SInt32,
+        "u32" => TypedArrayKind::UInt32,
+        "i64" => TypedArrayKind::SInt64,
+        "u64" => TypedArrayKind::UInt64,
+        "i128" => TypedArrayKind::SInt128,
+        "u128" => TypedArrayKind::UInt128,
+        "f16" => TypedArrayKind::Float16,
+        "bf16" => TypedArrayKind::BFloat16,
+        "f32" => TypedArrayKind::Float32,
+        "f64" => TypedArrayKind::Float64,
+        _ => return None,
+    })
+}
+
+fn invalid_layout(message: String) -> Error {
+    Error::Argument {
+        kind: ErrorKind::Type,
+        argument: Some(1),
+        message: format!("bad argument #1 to 'Struct' ({})", message),
+    }
+}
+
+fn no_field(name: &str) -> Error {
+    Error::Argument {
+        kind: ErrorKind::Type,
+        argument: None,
+        message: format!("struct has no field '{}'", name),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StructField {
+    pub name: String,
+    pub kind: TypedArrayKind,
+    /// Byte offset from the start of the record.
+    pub offset: usize,
+}
+
+/// The layout of a record made of scalar fields, laid out like a C struct
+/// declaring them in the same order, or without any padding if packed.
+#[derive(Debug, Clone)]
+pub struct StructLayout {
+    _fields: Rc<[StructField]>,
+    _size: usize,
+    _alignment: usize,
+}
+
+impl StructLayout {
+    /// Places `fields` in order, aligning each on its size unless `packed`,
+    /// and rounds the size up to the largest alignment.
+    pub fn new(fields: Vec<(String, TypedArrayKind)>, packed: bool) -> Result<Self, Error> {
+        if fields.is_empty() {
+            return Err(invalid_layout("struct has no fields".into()));
+        }
+        let mut placed: Vec<StructField> = Vec::with_capacity(fields.len());
+        let mut size = 0usize;
+        let mut alignment = 1;
+        for (name, kind) in fields {
+            if placed.iter().any(|field| field.name == name) {
+                return Err(invalid_layout(format!("duplicate field '{}'", name)));
+            }
+            let field_alignment = if packed { 1 } else { kind.bytes_per_element() };
+            alignment = alignment.max(field_alignment);
+            let offset = size.next_multiple_of(field_alignment);
+            size = offset + kind.bytes_per_element();
+            placed.push(StructField { name, kind, offset });
+        }
+        Ok(StructLayout {
+            _fields: placed.into(),
+            _size: size.next_multiple_of(alignment),
+            _alignment: alignment,
+        })
+    }
+
+    /// Reads a layout from a Lua table of `{ name, type }` pairs.
+    fn from_table(fields: mlua::Table, packed: bool) -> mlua::Result<Self> {
+        let mut pairs = Vec::new();
+        for (i, field) in fields.sequence_values::<mlua::Value>().enumerate() {
+            let field = match field? {
+                mlua::Value::Table(field) => field,
+                _ => return Err(invalid_layout(format!("field {} is not a table", i + 1)).into()),
+            };
+            let (name, kind) = match (
+                field.raw_get::<_, Option<String>>(1),
+                field.raw_get::<_, Option<String>>(2),
+            ) {
+                (Ok(Some(name)), Ok(Some(kind))) => (name, kind),
+                _ => {
+                    return Err(invalid_layout(format!(
+                        "field {} should be a {{ name, type }} pair",
+                        i + 1
+                    ))
+                    .into())
+                }
+            };
+            let kind = kind_from_name(&kind).ok_or_else(|| {
+                invalid_layout(format!("unknown type '{}' for field '{}'", kind, name))
+            })?;
+            pairs.push((name, kind));
+        }
+        Ok(StructLayout::new(pairs, packed)?)
+    }
+
+    pub fn fields(&self) -> &[StructField] {
+        &self._fields
+    }
+
+    pub fn field(&self, name: &str) -> Option<&StructField> {
+        self._fields.iter().find(|field| field.name == name)
+    }
+
+    /// Size of a record, padding included; the stride of a [`StructArray`].
+    pub fn size(&self) -> usize {
+        self._size
+    }
+
+    pub fn alignment(&self) -> usize {
+        self._alignment
+    }
+}
+
+impl mlua::UserData for StructLayout {
+    fn add_fields<'lua, F: mlua::UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("size", |_, this| Ok(this._size));
+        fields.add_field_method_get("alignment", |_, this| Ok(this._alignment));
+    }
+
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("offsetOf", |_, this, name: String| {
+            Ok(this.field(&name).ok_or_else(|| no_field(&name))?.offset)
+        });
+        methods.add_method("typeOf", |_, this, name: String| {
+            Ok(this
+                .field(&name)
+                .ok_or_else(|| no_field(&name))?
+                .kind
+                .to_string())
+        });
+        methods.add_meta_function(
+            mlua::MetaMethod::Call,
+            |_, args: (StructLayout, ArrayBufferLike, Option<usize>, Option<usize>)| {
+                let (layout, buffer, offset, length) = args;
+                Ok(StructArray::new(
+                    layout,
+                    buffer,
+                    offset.unwrap_or(0),
+                    length,
+                )?)
+            },
+        );
+    }
+}
+
+/// An array of records sharing the layout of a [`StructLayout`], viewed over
+/// a buffer.
+#[derive(Debug, Clone)]
+pub struct StructArray {
+    _layout: StructLayout,
+    _buffer: ArrayBufferLike,
+    _offset: usize,
+    /// `None` for views that cover as many records as the buffer holds.
+    _length: Option<usize>,
+}
+
+impl StructArray {
+    /// Views `length` records starting at the byte `offset`, or as many as
+    /// fit in the rest of the buffer if `length` is `None`.
+    pub fn new(
+        layout: StructLayout,
+        buffer: ArrayBufferLike,
+        offset: usize,
+        length: Option<usize>,
+    ) -> Result<Self, Error> {
+        buffer.check_attached()?;
+        if !offset.is_multiple_of(layout.alignment()) {
+            return Err(Error::Misaligned {
+                target: "Struct",
+                what: "start offset",
+                value: offset,
+                alignment: layout.alignment(),
+                argument: Some(2),
+            });
+        }
+        let end = match length {
+            Some(length) => length
+                .checked_mul(layout.size())
+                .and_then(|len| len.checked_add(offset))
+                .unwrap_or(usize::MAX),
+            None => offset,
+        };
+        if end > buffer.len() {
+            return Err(Error::OutOfBounds {
+                target: "ArrayBuffer",
+                offset: end,
+                len: buffer.len(),
+            });
+        }
+        Ok(StructArray {
+            _layout: layout,
+            _buffer: buffer,
+            _offset: offset,
+            _length: length,
+        })
+    }
+
+    pub fn layout(&self) -> &StructLayout {
+        &self._layout
+    }
+
+    pub fn buffer(&self) -> ArrayBufferLike {
+        self._buffer.clone()
+    }
+
+    /// Byte offset into the buffer, or 0 once the buffer is detached or has
+    /// shrunk below the start of a length-tracking view or the end of a
+    /// fixed-length one.
+    pub fn byte_offset(&self) -> usize {
+        let end = match self._length {
+            Some(length) => length
+                .checked_mul(self._layout.size())
+                .and_then(|len| len.checked_add(self._offset)),
+            None => Some(self._offset),
+        };
+        let in_bounds = end.is_some_and(|end| end <= self._buffer.len());
+        if in_bounds && !self._buffer.is_detached() {
+            self._offset
+        } else {
+            0
+        }
+    }
+
+    /// Number of records currently visible, 0 once a fixed-length view no
+    /// longer fits in its buffer.
+    pub fn len(&self) -> usize {
+        let available = self._buffer.len().saturating_sub(self._offset);
+        match self._length {
+            Some(length)
+                if length
+                    .checked_mul(self._layout.size())
+                    .is_some_and(|len| len <= available) =>
+            {
+                length
+            }
+            Some(_) => 0,
+            None => available / self._layout.size(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn byte_len(&self) -> usize {
+        self.len() * self._layout.size()
+    }
+
+    /// The field called `name` of the record at the 0-based `index`, as a
+    /// one-element typed array.
+    fn element(&self, index: usize, name: &str) -> Result<TypedArray, Error> {
+        self._buffer.check_attached()?;
+        let field = self._layout.field(name).ok_or_else(|| no_field(name))?;
+        if index >= self.len() {
+            return Err(Error::OutOfBounds {
+                target: "Struct",
+                offset: index + 1,
+                len: self.len(),
+            });
+        }
+        let offset = self._offset + index * self._layout.size() + field.offset;
+        Ok(TypedArray::element(
+            field.kind,
+            self._buffer.clone(),
+            offset,
+        ))
+    }
+
+    /// Reads the field `name` of the record at the 0-based `index`.
+    pub fn get(&self, index: usize, name: &str) -> Result<TypedArrayVariant, Error> {
+        let element = self.element(index, name)?;
+        element.get_variant(0).ok_or(Error::OutOfBounds {
+            target: "Struct",
+            offset: index + 1,
+            len: self.len(),
+        })
+    }
+
+    /// Converts `value` to the type of the field `name` and stores it in the
+    /// record at the 0-based `index`.
+    pub fn set(&self, index: usize, name: &str, value: Numeric) -> Result<(), Error> {
+        self.element(index, name)?.set_value(0, value)
+    }
+}
+
+impl std::fmt::Display for StructArray {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            fmt,
+            "StructArray {{ length: {}, size: {}, byteOffset: {} }}",
+            self.len(),
+            self._layout.size(),
+            self.byte_offset()
+        )
+    }
+}
+
+impl mlua::UserData for StructArray {
+    fn add_fields<'lua, F: mlua::UserDataFields<'lua, Self>>(fields: &mut F) {
+        fields.add_field_method_get("buffer", |_, this| Ok(this.buffer()));
+        fields.add_field_method_get("length", |_, this| Ok(this.len()));
+        fields.add_field_method_get("byteLength", |_, this| Ok(this.byte_len()));
+        fields.add_field_method_get("byteOffset", |_, this| Ok(this.byte_offset()));
+        fields.add_field_method_get("layout", |_, this| Ok(this._layout.clone()));
+    }
+
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_function(
+            mlua::MetaMethod::ToString,
+            |_, this: StructArray| -> Result<String, _> { Ok(format!("{this}")) },
+        );
+        methods.add_meta_function(
+            mlua::MetaMethod::Len,
+            |_, this: StructArray| -> Result<usize, _> { Ok(this.len()) },
+        );
+        methods.add_meta_function(
+            mlua::MetaMethod::Index,
+            |_, args: (StructArray, usize)| -> Result<Option<StructRef>, _> {
+                let (this, index) = args;
+                this._buffer.check_attached()?;
+                Ok(index
+                    .checked_sub(1)
+                    .filter(|index| *index < this.len())
+                    .map(|index| StructRef {
+                        _array: this,
+                        _index: index,
+                    }))
+            },
+        );
+        methods.add_meta_function(
+            mlua::MetaMethod::NewIndex,
+            |_, args: (StructArray, usize, mlua::Table)| -> Result<(), _> {
+                let (this, index, values) = args;
+                let index = index.checked_sub(1).ok_or(Error::OutOfBounds {
+                    target: "Struct",
+                    offset: 0,
+                    len: this.len(),
+                })?;
+                for field in this._layout.fields() {
+                    if let Some(value) = values.get::<_, Option<Numeric>>(field.name.as_str())? {
+                        this.set(index, &field.name, value)?;
+                    }
+                }
+                Ok(())
+            },
+        );
+    }
+}
+
+/// A record of a [`StructArray`], reading and writing its fields through
+/// the buffer.
+#[derive(Debug, Clone)]
+pub struct StructRef {
+    _array: StructArray,
+    _index: usize,
+}
+
+impl mlua::UserData for StructRef {
+    fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_meta_method(mlua::MetaMethod::ToString, |_, this, ()| {
+            let fields = this
+                ._array
+                ._layout
+                .fields()
+                .iter()
+                .map(|field| {
+                    let value = this._array.get(this._index, &field.name)?;
+                    Ok(format!("{} = {}", field.name, value))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            Ok(format!("{{ {} }}", fields.join(", ")))
+        });
+        methods.add_meta_method(mlua::MetaMethod::Index, |_, this, name: String| {
+            Ok(this._array.get(this._index, &name)?)
+        });
+        methods.add_meta_method(
+            mlua::MetaMethod::NewIndex,
+            |_, this, args: (String, Numeric)| {
+                let (name, value) = args;
+                Ok(this._array.set(this._index, &name, value)?)
+            },
+        );
+    }
+}
+
+/// Implements `memory.Struct(fields [, { packed = false }])`.
+pub(super) fn construct(
+    overloads: &super::Overloads,
+    args: &[mlua::Value],
+) -> mlua::Result<StructLayout> {
+    overloads.check_count(args, 2)?;
+    let fields = match args.first() {
+        Some(mlua::Value::Table(fields)) => fields.clone(),
+        value => return Err(overloads.type_error(1, "table", value.unwrap_or(&mlua::Value::Nil))),
+    };
+    let packed = match args.get(1) {
+        None | Some(mlua::Value::Nil) => false,
+        Some(mlua::Value::Table(options)) => {
+            options.get::<_, Option<bool>>("packed")?.unwrap_or(false)
+        }
+        Some(value) => return Err(overloads.type_error(2, "table", value)),
+    };
+    StructLayout::from_table(fields, packed)
+}
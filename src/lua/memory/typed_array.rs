@@ -1,87 +1,104 @@
+use super::typed_array_ref::{SharedBytes, TypedArrayRef, TypedArrayRefMut};
+use super::Error;
+
 pub trait TypedArrayElement: Sized {
+    /// Native-endian byte representation of an element.
+    type Bytes: AsRef<[u8]> + AsMut<[u8]> + Default;
+
     fn kind() -> TypedArrayKind;
 
-    fn index(buffer: &[u8], byte_offset: usize, index: usize) -> Option<std::ops::Range<usize>> {
-        let range = index * core::mem::size_of::<Self>() + byte_offset
-            ..(index + 1) * core::mem::size_of::<Self>() + byte_offset;
-        buffer.get(range.clone())?;
-        Some(range)
-    }
-
-    fn get(buffer: &[u8], byte_offset: usize, i: usize) -> Option<Self> {
-        let range = Self::index(buffer, byte_offset, i)?;
-        let mut result = core::mem::MaybeUninit::<Self>::uninit();
-        unsafe {
-            core::intrinsics::copy_nonoverlapping(
-                buffer[range].as_ptr() as *const core::mem::MaybeUninit<Self>,
-                &mut result as *mut _,
-                1,
-            )
-        };
-        Some(unsafe { result.assume_init() })
-    }
+    fn from_ne_bytes(bytes: Self::Bytes) -> Self;
 
-    fn set(buffer: &[u8], byte_offset: usize, i: usize, this: Self) -> Result<(), ()> {
-        let range = Self::index(buffer, byte_offset, i).ok_or(())?;
-        unsafe {
-            core::intrinsics::copy_nonoverlapping(
-                &this as *const Self,
-                buffer[range].as_ptr() as *mut Self,
-                1,
-            )
-        };
-        Ok(())
-    }
-}
+    fn to_ne_bytes(self) -> Self::Bytes;
 
-impl TypedArrayElement for i8 {
-    fn kind() -> TypedArrayKind {
-        TypedArrayKind::SInt8
-    }
-}
-impl TypedArrayElement for u8 {
-    fn kind() -> TypedArrayKind {
-        TypedArrayKind::UInt8
+    fn index(byte_offset: usize, index: usize) -> Option<usize> {
+        index
+            .checked_mul(core::mem::size_of::<Self>())?
+            .checked_add(byte_offset)
     }
-}
-impl TypedArrayElement for i16 {
-    fn kind() -> TypedArrayKind {
-        TypedArrayKind::SInt16
-    }
-}
-impl TypedArrayElement for u16 {
-    fn kind() -> TypedArrayKind {
-        TypedArrayKind::UInt16
+
+    fn get(buffer: &super::ArrayBufferLike, byte_offset: usize, i: usize) -> Option<Self> {
+        let mut bytes = Self::Bytes::default();
+        buffer.read_into(Self::index(byte_offset, i)?, bytes.as_mut())?;
+        Some(Self::from_ne_bytes(bytes))
     }
-}
-impl TypedArrayElement for i32 {
-    fn kind() -> TypedArrayKind {
-        TypedArrayKind::SInt32
+
+    fn set(
+        buffer: &super::ArrayBufferLike,
+        byte_offset: usize,
+        i: usize,
+        this: Self,
+    ) -> Option<()> {
+        buffer.write_from(Self::index(byte_offset, i)?, this.to_ne_bytes().as_ref())
     }
 }
-impl TypedArrayElement for u32 {
-    fn kind() -> TypedArrayKind {
-        TypedArrayKind::UInt32
-    }
+
+macro_rules! impl_typed_array_element {
+    ($type:ty, $kind:ident) => {
+        impl TypedArrayElement for $type {
+            type Bytes = [u8; core::mem::size_of::<$type>()];
+
+            fn kind() -> TypedArrayKind {
+                TypedArrayKind::$kind
+            }
+
+            fn from_ne_bytes(bytes: Self::Bytes) -> Self {
+                <$type>::from_ne_bytes(bytes)
+            }
+
+            fn to_ne_bytes(self) -> Self::Bytes {
+                <$type>::to_ne_bytes(self)
+            }
+        }
+    };
 }
-impl TypedArrayElement for i64 {
+
+impl_typed_array_element!(i8, SInt8);
+impl_typed_array_element!(u8, UInt8);
+impl_typed_array_element!(i16, SInt16);
+impl_typed_array_element!(u16, UInt16);
+impl_typed_array_element!(i32, SInt32);
+impl_typed_array_element!(u32, UInt32);
+impl_typed_array_element!(i64, SInt64);
+impl_typed_array_element!(u64, UInt64);
+impl_typed_array_element!(i128, SInt128);
+impl_typed_array_element!(u128, UInt128);
+impl_typed_array_element!(half::f16, Float16);
+impl_typed_array_element!(half::bf16, BFloat16);
+impl_typed_array_element!(f32, Float32);
+impl_typed_array_element!(f64, Float64);
+
+/// Element of a `UInt8ClampedArray`: a byte that saturates instead of
+/// wrapping when assigned out-of-range values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[repr(transparent)]
+pub struct ClampedU8(pub u8);
+
+impl TypedArrayElement for ClampedU8 {
+    type Bytes = [u8; 1];
+
     fn kind() -> TypedArrayKind {
-        TypedArrayKind::SInt64
+        TypedArrayKind::UInt8Clamped
     }
-}
-impl TypedArrayElement for u64 {
-    fn kind() -> TypedArrayKind {
-        TypedArrayKind::UInt64
+
+    fn from_ne_bytes(bytes: Self::Bytes) -> Self {
+        ClampedU8(u8::from_ne_bytes(bytes))
     }
-}
-impl TypedArrayElement for f32 {
-    fn kind() -> TypedArrayKind {
-        TypedArrayKind::Float32
+
+    fn to_ne_bytes(self) -> Self::Bytes {
+        self.0.to_ne_bytes()
     }
 }
-impl TypedArrayElement for f64 {
-    fn kind() -> TypedArrayKind {
-        TypedArrayKind::Float64
+
+impl From<Numeric> for ClampedU8 {
+    /// Rounds half to even and clamps to `0..=255`; NaN becomes 0.
+    fn from(value: Numeric) -> Self {
+        ClampedU8(match value {
+            Numeric::Integer(v) => v.clamp(0, 255) as u8,
+            Numeric::Wide(v) => v.clamp(0, 255) as u8,
+            Numeric::WideUnsigned(v) => v.min(255) as u8,
+            Numeric::Number(v) => v.round_ties_even().clamp(0.0, 255.0) as u8,
+        })
     }
 }
 
@@ -89,27 +106,62 @@ impl TypedArrayElement for f64 {
 pub enum TypedArrayKind {
     SInt8,
     UInt8,
+    UInt8Clamped,
     SInt16,
     UInt16,
     SInt32,
     UInt32,
     SInt64,
+    /// Elements are read as Lua integers holding the same 64 bits, so values
+    /// above `math.maxinteger` come back negative, as `string.unpack("J")`
+    /// returns them: `2^64 - 1` stored under any policy reads as `-1`. Compare
+    /// them with `math.ult`; `join` and `toString` print them unsigned.
     UInt64,
+    SInt128,
+    UInt128,
+    Float16,
+    BFloat16,
     Float32,
     Float64,
 }
 
 impl TypedArrayKind {
+    /// Name of the typed array constructor for this kind, e.g. `Int8Array`.
+    pub fn name(self) -> &'static str {
+        match self {
+            TypedArrayKind::SInt8 => "Int8Array",
+            TypedArrayKind::UInt8 => "UInt8Array",
+            TypedArrayKind::UInt8Clamped => "UInt8ClampedArray",
+            TypedArrayKind::SInt16 => "Int16Array",
+            TypedArrayKind::UInt16 => "UInt16Array",
+            TypedArrayKind::SInt32 => "Int32Array",
+            TypedArrayKind::UInt32 => "UInt32Array",
+            TypedArrayKind::SInt64 => "Int64Array",
+            TypedArrayKind::UInt64 => "UInt64Array",
+            TypedArrayKind::SInt128 => "Int128Array",
+            TypedArrayKind::UInt128 => "UInt128Array",
+            TypedArrayKind::Float16 => "Float16Array",
+            TypedArrayKind::BFloat16 => "BFloat16Array",
+            TypedArrayKind::Float32 => "Float32Array",
+            TypedArrayKind::Float64 => "Float64Array",
+        }
+    }
+
     pub const fn bytes_per_element(self) -> usize {
         match self {
             TypedArrayKind::SInt8 => core::mem::size_of::<i8>(),
             TypedArrayKind::UInt8 => core::mem::size_of::<u8>(),
+            TypedArrayKind::UInt8Clamped => core::mem::size_of::<ClampedU8>(),
             TypedArrayKind::SInt16 => core::mem::size_of::<i16>(),
             TypedArrayKind::UInt16 => core::mem::size_of::<u16>(),
             TypedArrayKind::SInt32 => core::mem::size_of::<i32>(),
             TypedArrayKind::UInt32 => core::mem::size_of::<u32>(),
             TypedArrayKind::SInt64 => core::mem::size_of::<i64>(),
             TypedArrayKind::UInt64 => core::mem::size_of::<u64>(),
+            TypedArrayKind::SInt128 => core::mem::size_of::<i128>(),
+            TypedArrayKind::UInt128 => core::mem::size_of::<u128>(),
+            TypedArrayKind::Float16 => core::mem::size_of::<half::f16>(),
+            TypedArrayKind::BFloat16 => core::mem::size_of::<half::bf16>(),
             TypedArrayKind::Float32 => core::mem::size_of::<f32>(),
             TypedArrayKind::Float64 => core::mem::size_of::<f64>(),
         }
@@ -121,12 +173,17 @@ impl std::fmt::Display for TypedArrayKind {
         fmt.write_str(match self {
             TypedArrayKind::SInt8 => "Int8",
             TypedArrayKind::UInt8 => "UInt8",
+            TypedArrayKind::UInt8Clamped => "UInt8Clamped",
             TypedArrayKind::SInt16 => "Int16",
             TypedArrayKind::UInt16 => "UInt16",
             TypedArrayKind::SInt32 => "Int32",
             TypedArrayKind::UInt32 => "UInt32",
             TypedArrayKind::SInt64 => "Int64",
             TypedArrayKind::UInt64 => "UInt64",
+            TypedArrayKind::SInt128 => "Int128",
+            TypedArrayKind::UInt128 => "UInt128",
+            TypedArrayKind::Float16 => "Float16",
+            TypedArrayKind::BFloat16 => "BFloat16",
             TypedArrayKind::Float32 => "Float32",
             TypedArrayKind::Float64 => "Float64",
         })
@@ -136,21 +193,28 @@ impl std::fmt::Display for TypedArrayKind {
 #[derive(Debug, Clone)]
 pub struct TypedArray {
     _kind: TypedArrayKind,
-    _buffer: super::ArrayBuffer,
+    _buffer: super::ArrayBufferLike,
     _offset: usize,
-    _length: usize,
+    /// `None` for views over a resizable buffer that track its length.
+    _length: Option<usize>,
+    _overflow: OverflowPolicy,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum TypedArrayVariant {
     SInt8(i8),
     UInt8(u8),
+    UInt8Clamped(u8),
     SInt16(i16),
     UInt16(u16),
     SInt32(i32),
     UInt32(u32),
     SInt64(i64),
     UInt64(u64),
+    SInt128(i128),
+    UInt128(u128),
+    Float16(half::f16),
+    BFloat16(half::bf16),
     Float32(f32),
     Float64(f64),
 }
@@ -160,310 +224,1108 @@ impl std::fmt::Display for TypedArrayVariant {
         match self {
             TypedArrayVariant::SInt8(v) => write!(fmt, "{}", v),
             TypedArrayVariant::UInt8(v) => write!(fmt, "{}", v),
+            TypedArrayVariant::UInt8Clamped(v) => write!(fmt, "{}", v),
             TypedArrayVariant::SInt16(v) => write!(fmt, "{}", v),
             TypedArrayVariant::UInt16(v) => write!(fmt, "{}", v),
             TypedArrayVariant::SInt32(v) => write!(fmt, "{}", v),
             TypedArrayVariant::UInt32(v) => write!(fmt, "{}", v),
             TypedArrayVariant::SInt64(v) => write!(fmt, "{}", v),
             TypedArrayVariant::UInt64(v) => write!(fmt, "{}", v),
+            TypedArrayVariant::SInt128(v) => write!(fmt, "{}", v),
+            TypedArrayVariant::UInt128(v) => write!(fmt, "{}", v),
+            TypedArrayVariant::Float16(v) => write!(fmt, "{}", v),
+            TypedArrayVariant::BFloat16(v) => write!(fmt, "{}", v),
             TypedArrayVariant::Float32(v) => write!(fmt, "{}", v),
             TypedArrayVariant::Float64(v) => write!(fmt, "{}", v),
         }
     }
 }
 
-pub struct RangeError(String);
+impl<'lua> mlua::ToLua<'lua> for TypedArrayVariant {
+    /// Integer elements become Lua integers; `UInt64` values above
+    /// `math.maxinteger` wrap around like `string.unpack("J")` does. 128-bit
+    /// elements become `Int128`/`UInt128` userdata.
+    fn to_lua(self, lua: &'lua mlua::Lua) -> mlua::Result<mlua::Value<'lua>> {
+        Ok(match self {
+            TypedArrayVariant::SInt8(v) => mlua::Value::Integer(v.into()),
+            TypedArrayVariant::UInt8(v) => mlua::Value::Integer(v.into()),
+            TypedArrayVariant::UInt8Clamped(v) => mlua::Value::Integer(v.into()),
+            TypedArrayVariant::SInt16(v) => mlua::Value::Integer(v.into()),
+            TypedArrayVariant::UInt16(v) => mlua::Value::Integer(v.into()),
+            TypedArrayVariant::SInt32(v) => mlua::Value::Integer(v.into()),
+            TypedArrayVariant::UInt32(v) => mlua::Value::Integer(v.into()),
+            TypedArrayVariant::SInt64(v) => mlua::Value::Integer(v),
+            TypedArrayVariant::UInt64(v) => mlua::Value::Integer(v as mlua::Integer),
+            TypedArrayVariant::SInt128(v) => {
+                mlua::Value::UserData(lua.create_userdata(super::Int128(v))?)
+            }
+            TypedArrayVariant::UInt128(v) => {
+                mlua::Value::UserData(lua.create_userdata(super::UInt128(v))?)
+            }
+            TypedArrayVariant::Float16(v) => mlua::Value::Number(v.into()),
+            TypedArrayVariant::BFloat16(v) => mlua::Value::Number(v.into()),
+            TypedArrayVariant::Float32(v) => mlua::Value::Number(v.into()),
+            TypedArrayVariant::Float64(v) => mlua::Value::Number(v),
+        })
+    }
+}
+
+/// A Lua number as assigned to a typed array, keeping integers exact.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Numeric {
+    Integer(mlua::Integer),
+    Number(mlua::Number),
+    /// An `Int128` value.
+    Wide(i128),
+    /// A `UInt128` value, kept apart so that values above `i128::MAX` stay
+    /// positive.
+    WideUnsigned(u128),
+}
+
+impl Numeric {
+    pub fn to_number(self) -> mlua::Number {
+        match self {
+            Numeric::Integer(v) => v as mlua::Number,
+            Numeric::Number(v) => v,
+            Numeric::Wide(v) => v as mlua::Number,
+            Numeric::WideUnsigned(v) => v as mlua::Number,
+        }
+    }
+
+    /// Whether both values are the same number, comparing integers exactly
+    /// even when one side is a float or a 128-bit value. NaN equals nothing.
+    pub fn equals(self, other: Numeric) -> bool {
+        match (self, other) {
+            (Numeric::Number(a), Numeric::Number(b)) => a == b,
+            _ => self.exact_integer().is_some() && self.exact_integer() == other.exact_integer(),
+        }
+    }
+
+    /// The sign and magnitude of integral values below 2^128.
+    fn exact_integer(self) -> Option<(bool, u128)> {
+        match self {
+            Numeric::Integer(v) => Some((v < 0, v.unsigned_abs().into())),
+            Numeric::Wide(v) => Some((v < 0, v.unsigned_abs())),
+            Numeric::WideUnsigned(v) => Some((false, v)),
+            Numeric::Number(v) if v.fract() == 0.0 && v.abs() < 2f64.powi(128) => {
+                Some((v < 0.0, v.abs() as u128))
+            }
+            Numeric::Number(_) => None,
+        }
+    }
+
+    /// Truncates to an integer and fits it into `min..=max` following
+    /// `policy`, or returns `None` if the policy rejects the value.
+    ///
+    /// Wrapped values are returned out of range; the caller keeps their low
+    /// bits with an `as` cast.
+    pub(super) fn to_integer(self, min: i128, max: i128, policy: OverflowPolicy) -> Option<i128> {
+        let wide = match self {
+            Numeric::Integer(v) => v as i128,
+            Numeric::Wide(v) => v,
+            // Values above `i128::MAX` are out of range for every caller.
+            Numeric::WideUnsigned(v) => match i128::try_from(v) {
+                Ok(v) => v,
+                Err(_) => {
+                    return match policy {
+                        OverflowPolicy::Error => None,
+                        OverflowPolicy::Wrap => Some(v as i128),
+                        OverflowPolicy::Clamp => Some(max),
+                    }
+                }
+            },
+            Numeric::Number(v) if v.is_nan() => {
+                return (policy != OverflowPolicy::Error).then_some(0)
+            }
+            Numeric::Number(v) if v.is_infinite() => {
+                return match policy {
+                    OverflowPolicy::Error => None,
+                    OverflowPolicy::Wrap => Some(0),
+                    OverflowPolicy::Clamp => Some(if v > 0.0 { max } else { min }),
+                }
+            }
+            // The remainder is exact and keeps the low 128 bits intact; folding
+            // it into the range of i128 is exact as well.
+            Numeric::Number(v) if policy == OverflowPolicy::Wrap => {
+                let v = v.trunc() % 2f64.powi(128);
+                (if v >= 2f64.powi(127) {
+                    v - 2f64.powi(128)
+                } else if v < -2f64.powi(127) {
+                    v + 2f64.powi(128)
+                } else {
+                    v
+                }) as i128
+            }
+            // Checked before the cast, which would saturate at the bounds of
+            // i128 instead.
+            Numeric::Number(v) => {
+                let v = v.trunc();
+                if (min as f64..max as f64 + 1.0).contains(&v) {
+                    v as i128
+                } else if policy == OverflowPolicy::Clamp {
+                    return Some(if v < 0.0 { min } else { max });
+                } else {
+                    return None;
+                }
+            }
+        };
+        if (min..=max).contains(&wide) {
+            Some(wide)
+        } else {
+            match policy {
+                OverflowPolicy::Error => None,
+                OverflowPolicy::Wrap => Some(wide),
+                OverflowPolicy::Clamp => Some(wide.clamp(min, max)),
+            }
+        }
+    }
+}
+
+impl From<TypedArrayVariant> for Numeric {
+    fn from(variant: TypedArrayVariant) -> Self {
+        match variant {
+            TypedArrayVariant::SInt8(v) => Numeric::Integer(v.into()),
+            TypedArrayVariant::UInt8(v) => Numeric::Integer(v.into()),
+            TypedArrayVariant::UInt8Clamped(v) => Numeric::Integer(v.into()),
+            TypedArrayVariant::SInt16(v) => Numeric::Integer(v.into()),
+            TypedArrayVariant::UInt16(v) => Numeric::Integer(v.into()),
+            TypedArrayVariant::SInt32(v) => Numeric::Integer(v.into()),
+            TypedArrayVariant::UInt32(v) => Numeric::Integer(v.into()),
+            TypedArrayVariant::SInt64(v) => Numeric::Integer(v),
+            TypedArrayVariant::UInt64(v) => Numeric::Integer(v as mlua::Integer),
+            TypedArrayVariant::SInt128(v) => Numeric::Wide(v),
+            TypedArrayVariant::UInt128(v) => Numeric::WideUnsigned(v),
+            TypedArrayVariant::Float16(v) => Numeric::Number(v.into()),
+            TypedArrayVariant::BFloat16(v) => Numeric::Number(v.into()),
+            TypedArrayVariant::Float32(v) => Numeric::Number(v.into()),
+            TypedArrayVariant::Float64(v) => Numeric::Number(v),
+        }
+    }
+}
+
+impl<'lua> mlua::FromLua<'lua> for Numeric {
+    fn from_lua(value: mlua::Value<'lua>, _: &'lua mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::Integer(v) => Ok(Numeric::Integer(v)),
+            mlua::Value::Number(v) => Ok(Numeric::Number(v)),
+            mlua::Value::UserData(ud) if ud.is::<super::Int128>() => {
+                Ok(Numeric::Wide(ud.borrow::<super::Int128>()?.0))
+            }
+            mlua::Value::UserData(ud) if ud.is::<super::UInt128>() => {
+                Ok(Numeric::WideUnsigned(ud.borrow::<super::UInt128>()?.0))
+            }
+            value => Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "number",
+                message: None,
+            }),
+        }
+    }
+}
+
+/// How integer arrays store values that do not fit their element type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Reject the assignment.
+    Error,
+    /// Keep the low bits, like JS typed arrays do. NaN and infinities
+    /// become 0.
+    #[default]
+    Wrap,
+    /// Saturate to the closest representable value. NaN becomes 0.
+    Clamp,
+}
 
-impl RangeError {
-    pub(crate) fn new(msg: String) -> Self {
-        RangeError(msg)
+impl OverflowPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            OverflowPolicy::Error => "error",
+            OverflowPolicy::Wrap => "wrap",
+            OverflowPolicy::Clamp => "clamp",
+        }
     }
 
-    pub fn message(self) -> String {
-        self.0
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(OverflowPolicy::Error),
+            "wrap" => Some(OverflowPolicy::Wrap),
+            "clamp" => Some(OverflowPolicy::Clamp),
+            _ => None,
+        }
+    }
+
+    /// Like [`OverflowPolicy::from_name`], for the `overflow` property of
+    /// views.
+    pub(super) fn parse(name: &str) -> mlua::Result<Self> {
+        Self::from_name(name).ok_or_else(|| {
+            mlua::Error::RuntimeError(format!(
+                "unknown overflow policy '{}', expected 'error', 'wrap' or 'clamp'",
+                name
+            ))
+        })
+    }
+}
+
+/// A misaligned offset or length, derived from constructor argument
+/// `argument`.
+fn misaligned(kind: TypedArrayKind, what: &'static str, value: usize, argument: usize) -> Error {
+    Error::Misaligned {
+        target: kind.name(),
+        what,
+        value,
+        alignment: kind.bytes_per_element(),
+        argument: Some(argument),
+    }
+}
+
+fn out_of_bounds(buffer: &super::ArrayBufferLike, end: usize) -> Error {
+    Error::OutOfBounds {
+        target: "ArrayBuffer",
+        offset: end,
+        len: buffer.len(),
     }
 }
 
 impl TypedArray {
     pub fn new(
         kind: TypedArrayKind,
-        buffer: super::ArrayBuffer,
+        buffer: super::ArrayBufferLike,
         offset: usize,
         length: usize,
-    ) -> Result<Self, RangeError> {
-        if offset % kind.bytes_per_element() != 0 {
-            Err(RangeError::new(format!(
-                "start offset of {}Array should be a multiple of {}",
-                kind,
-                kind.bytes_per_element()
-            )))
-        } else if offset + length * kind.bytes_per_element() > buffer.len() {
-            Err(RangeError::new(format!(
-                "attempting to construct out-of-bounds {}Array on ArrayBuffer",
-                kind
-            )))
+    ) -> Result<Self, Error> {
+        buffer.check_attached()?;
+        let end = length
+            .checked_mul(kind.bytes_per_element())
+            .and_then(|len| len.checked_add(offset))
+            .unwrap_or(usize::MAX);
+        if !offset.is_multiple_of(kind.bytes_per_element()) {
+            Err(misaligned(kind, "start offset", offset, 2))
+        } else if end > buffer.len() {
+            Err(out_of_bounds(&buffer, end))
         } else {
             Ok(TypedArray {
                 _kind: kind,
                 _buffer: buffer,
                 _offset: offset,
-                _length: length,
+                _length: Some(length),
+                _overflow: OverflowPolicy::default(),
             })
         }
     }
 
     pub fn with_offset(
         kind: TypedArrayKind,
-        buffer: super::ArrayBuffer,
+        buffer: super::ArrayBufferLike,
         offset: usize,
-    ) -> Result<Self, RangeError> {
-        buffer
+    ) -> Result<Self, Error> {
+        buffer.check_attached()?;
+        if !offset.is_multiple_of(kind.bytes_per_element()) {
+            return Err(misaligned(kind, "start offset", offset, 2));
+        }
+        if buffer.is_resizable() {
+            return if offset > buffer.len() {
+                Err(out_of_bounds(&buffer, offset))
+            } else {
+                Ok(TypedArray {
+                    _kind: kind,
+                    _buffer: buffer,
+                    _offset: offset,
+                    _length: None,
+                    _overflow: OverflowPolicy::default(),
+                })
+            };
+        }
+        let byte_length = buffer
             .len()
             .checked_sub(offset)
-            .filter(|byte_length| byte_length % kind.bytes_per_element() == 0)
-            .map(|byte_length| byte_length / kind.bytes_per_element())
-            .map(|length| TypedArray {
-                _kind: kind,
-                _buffer: buffer,
-                _offset: offset,
-                _length: length,
-            })
-            .ok_or_else(|| {
-                RangeError::new(format!(
-                    "attempting to construct out-of-bounds {}Array on ArrayBuffer",
-                    kind
-                ))
-            })
+            .ok_or_else(|| out_of_bounds(&buffer, offset))?;
+        if !byte_length.is_multiple_of(kind.bytes_per_element()) {
+            return Err(misaligned(kind, "byte length", byte_length, 1));
+        }
+        Ok(TypedArray {
+            _kind: kind,
+            _buffer: buffer,
+            _offset: offset,
+            _length: Some(byte_length / kind.bytes_per_element()),
+            _overflow: OverflowPolicy::default(),
+        })
     }
 
-    pub fn with_len(
-        kind: TypedArrayKind,
-        length: usize,
-    ) -> Result<Self, std::collections::TryReserveError> {
-        let buffer = super::ArrayBuffer::new(length * kind.bytes_per_element())?;
+    pub fn with_len(kind: TypedArrayKind, length: usize) -> Result<Self, Error> {
+        let byte_length = length
+            .checked_mul(kind.bytes_per_element())
+            .ok_or(Error::Allocation)?;
+        let buffer = super::ArrayBuffer::new(byte_length)?;
         Ok(TypedArray {
             _kind: kind,
-            _buffer: buffer,
+            _buffer: buffer.into(),
             _offset: 0,
-            _length: length,
+            _length: Some(length),
+            _overflow: OverflowPolicy::default(),
         })
     }
 
+    /// Copies the elements of `source` into a new array, converting them to
+    /// `kind`.
+    pub fn from_array(kind: TypedArrayKind, source: &TypedArray) -> mlua::Result<Self> {
+        let mut array = TypedArray::with_len(kind, source.len())?;
+        array.set_from(source, 1)?;
+        Ok(array)
+    }
+
+    pub fn from_values(kind: TypedArrayKind, values: &[Numeric]) -> mlua::Result<Self> {
+        let mut array = TypedArray::with_len(kind, values.len())?;
+        array.set_from_values(values, 1)?;
+        Ok(array)
+    }
+
     pub fn with_buffer(
         kind: TypedArrayKind,
-        buffer: super::ArrayBuffer,
-    ) -> Result<Self, RangeError> {
-        if buffer.len() % kind.bytes_per_element() != 0 {
-            Err(RangeError::new(format!(
-                "buffer length for {}Array should be a multiple of {}",
-                kind,
-                kind.bytes_per_element()
-            )))
+        buffer: super::ArrayBufferLike,
+    ) -> Result<Self, Error> {
+        buffer.check_attached()?;
+        if buffer.is_resizable() {
+            Ok(TypedArray {
+                _kind: kind,
+                _buffer: buffer,
+                _offset: 0,
+                _length: None,
+                _overflow: OverflowPolicy::default(),
+            })
+        } else if !buffer.len().is_multiple_of(kind.bytes_per_element()) {
+            Err(misaligned(kind, "buffer length", buffer.len(), 1))
         } else {
             Ok(TypedArray {
                 _kind: kind,
-                _length: buffer.len() / kind.bytes_per_element(),
+                _length: Some(buffer.len() / kind.bytes_per_element()),
+                _overflow: OverflowPolicy::default(),
                 _buffer: buffer,
                 _offset: 0,
             })
         }
     }
 
+    /// A view of the single element at the byte `offset` of `buffer`, which
+    /// unlike with the public constructors need not be aligned.
+    pub(super) fn element(
+        kind: TypedArrayKind,
+        buffer: super::ArrayBufferLike,
+        offset: usize,
+    ) -> Self {
+        TypedArray {
+            _kind: kind,
+            _buffer: buffer,
+            _offset: offset,
+            _length: Some(1),
+            _overflow: OverflowPolicy::default(),
+        }
+    }
+
+    pub fn kind(&self) -> TypedArrayKind {
+        self._kind
+    }
+
+    /// Byte offset into the buffer, or 0 once the buffer is detached.
     pub fn byte_offset(&self) -> usize {
-        self._offset
+        if self.is_detached() {
+            0
+        } else {
+            self._offset
+        }
     }
 
     pub fn byte_len(&self) -> usize {
-        self._length * self._kind.bytes_per_element()
+        self.len() * self._kind.bytes_per_element()
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self._buffer.is_detached()
     }
 
+    /// Number of elements currently visible through the view.
+    ///
+    /// Length-tracking views follow the buffer, while fixed-length views
+    /// report 0 once the buffer shrinks below their end.
     pub fn len(&self) -> usize {
-        self._length
+        let available = self._buffer.len().saturating_sub(self._offset);
+        match self._length {
+            Some(length)
+                if length
+                    .checked_mul(self._kind.bytes_per_element())
+                    .is_some_and(|len| len <= available) =>
+            {
+                length
+            }
+            Some(_) => 0,
+            None => available / self._kind.bytes_per_element(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_length_tracking(&self) -> bool {
+        self._length.is_none()
     }
 
-    pub fn buffer(&self) -> super::ArrayBuffer {
+    pub fn buffer(&self) -> super::ArrayBufferLike {
         self._buffer.clone()
     }
 
-    pub fn name(&self) -> &'static str {
+    /// Creates a view over the elements `begin..=end` sharing this buffer.
+    ///
+    /// Bounds are 1-based and negative values count from the end, see
+    /// `relative_range`. Without `end`, a view over a length-tracking array
+    /// tracks the length of the buffer too.
+    pub fn subarray(
+        &self,
+        begin: Option<mlua::Integer>,
+        end: Option<mlua::Integer>,
+    ) -> Result<Self, Error> {
+        self._buffer.check_attached()?;
+        let range = super::relative_range(self.len(), begin, end);
+        let tracking = self.is_length_tracking() && end.is_none();
+        Ok(TypedArray {
+            _kind: self._kind,
+            _buffer: self._buffer.clone(),
+            _offset: self._offset + range.start * self._kind.bytes_per_element(),
+            _length: if tracking { None } else { Some(range.len()) },
+            _overflow: self._overflow,
+        })
+    }
+
+    /// Copies the elements `begin..=end` into a new array of the same kind.
+    pub fn slice(
+        &self,
+        begin: Option<mlua::Integer>,
+        end: Option<mlua::Integer>,
+    ) -> mlua::Result<Self> {
+        let view = self.subarray(begin, end)?;
+        let result = TypedArray::with_len(self._kind, view.len())?;
+        let mut bytes = vec![0; view.byte_len()];
+        self._buffer.read_into(view._offset, &mut bytes);
+        result._buffer.write_from(0, &bytes);
+        Ok(result)
+    }
+
+    /// Sets the elements `begin..=end` (the whole array by default) to `value`.
+    pub fn fill(
+        &mut self,
+        value: Numeric,
+        begin: Option<mlua::Integer>,
+        end: Option<mlua::Integer>,
+    ) -> mlua::Result<()> {
+        let view = self.subarray(begin, end)?;
+        self._buffer.check_writable()?;
+        let element = self.encode(value)?;
+        let pattern = &element[..self._kind.bytes_per_element()];
+        self._buffer
+            .write_from(view._offset, &pattern.repeat(view.len()));
+        Ok(())
+    }
+
+    /// Copies `source` into this array starting at the 1-based `index`,
+    /// converting between kinds if needed.
+    ///
+    /// The source is read completely before anything is written, so both
+    /// arrays may share the same buffer.
+    pub fn set_from(&mut self, source: &TypedArray, index: usize) -> mlua::Result<()> {
+        self._buffer.check_writable()?;
+        source._buffer.check_attached()?;
+        let start = self.check_fits(source.len(), index)?;
+        if source._kind == self._kind {
+            let mut bytes = vec![0; source.byte_len()];
+            source._buffer.read_into(source._offset, &mut bytes);
+            self._buffer.write_from(
+                self._offset + start * self._kind.bytes_per_element(),
+                &bytes,
+            );
+        } else {
+            let values = source.iter().collect::<Vec<_>>();
+            for (i, value) in values.into_iter().enumerate() {
+                self.set_value(start + i, value.into())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies `values` into this array starting at the 1-based `index`.
+    pub fn set_from_values(&mut self, values: &[Numeric], index: usize) -> mlua::Result<()> {
+        self._buffer.check_writable()?;
+        let start = self.check_fits(values.len(), index)?;
+        for (i, value) in values.iter().enumerate() {
+            self.set_value(start + i, *value)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the element at `index` reads back as `value`, see
+    /// [`Numeric::equals`].
+    fn element_equals(&self, index: usize, value: Numeric) -> bool {
+        self.get_variant(index)
+            .is_some_and(|element| Numeric::from(element).equals(value))
+    }
+
+    /// Returns the 1-based index of the first element equal to `value`,
+    /// searching from `from` onwards.
+    pub fn index_of(&self, value: Numeric, from: Option<mlua::Integer>) -> Option<usize> {
+        super::relative_range(self.len(), from, None)
+            .find(|i| self.element_equals(*i, value))
+            .map(|i| i + 1)
+    }
+
+    /// Returns the 1-based index of the last element equal to `value`,
+    /// searching backwards from `from`.
+    pub fn last_index_of(&self, value: Numeric, from: Option<mlua::Integer>) -> Option<usize> {
+        super::relative_range(self.len(), None, from)
+            .rev()
+            .find(|i| self.element_equals(*i, value))
+            .map(|i| i + 1)
+    }
+
+    /// Like [`TypedArray::index_of`], but NaN is found as well.
+    pub fn includes(&self, value: Numeric, from: Option<mlua::Integer>) -> bool {
+        let nan = matches!(value, Numeric::Number(v) if v.is_nan());
+        super::relative_range(self.len(), from, None).any(|i| {
+            self.get_number(i)
+                .is_some_and(|number| nan && number.is_nan())
+                || self.element_equals(i, value)
+        })
+    }
+
+    pub fn join(&self, separator: &str) -> String {
+        self.iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
+    /// Reverses the elements in place.
+    pub fn reverse(&mut self) -> Result<(), Error> {
+        self._buffer.check_writable()?;
+        let mut bytes = vec![0; self.byte_len()];
+        self._buffer.read_into(self._offset, &mut bytes);
+        let reversed = bytes
+            .chunks(self._kind.bytes_per_element())
+            .rev()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        self._buffer.write_from(self._offset, &reversed);
+        Ok(())
+    }
+
+    /// Sorts the elements in ascending numeric order, with NaN last and -0
+    /// before +0.
+    pub fn sort(&mut self) -> Result<(), Error> {
+        self._buffer.check_writable()?;
         match self._kind {
-            TypedArrayKind::SInt8 => "Int8Array",
-            TypedArrayKind::UInt8 => "UInt8Array",
-            TypedArrayKind::SInt16 => "Int16Array",
-            TypedArrayKind::UInt16 => "UInt16Array",
-            TypedArrayKind::SInt32 => "Int32Array",
-            TypedArrayKind::UInt32 => "UInt32Array",
-            TypedArrayKind::SInt64 => "Int64Array",
-            TypedArrayKind::UInt64 => "UInt64Array",
-            TypedArrayKind::Float32 => "Float32Array",
-            TypedArrayKind::Float64 => "Float64Array",
+            TypedArrayKind::SInt8 => self.sort_elements::<i8>(Ord::cmp),
+            TypedArrayKind::UInt8 => self.sort_elements::<u8>(Ord::cmp),
+            TypedArrayKind::UInt8Clamped => self.sort_elements::<ClampedU8>(Ord::cmp),
+            TypedArrayKind::SInt16 => self.sort_elements::<i16>(Ord::cmp),
+            TypedArrayKind::UInt16 => self.sort_elements::<u16>(Ord::cmp),
+            TypedArrayKind::SInt32 => self.sort_elements::<i32>(Ord::cmp),
+            TypedArrayKind::UInt32 => self.sort_elements::<u32>(Ord::cmp),
+            TypedArrayKind::SInt64 => self.sort_elements::<i64>(Ord::cmp),
+            TypedArrayKind::UInt64 => self.sort_elements::<u64>(Ord::cmp),
+            TypedArrayKind::SInt128 => self.sort_elements::<i128>(Ord::cmp),
+            TypedArrayKind::UInt128 => self.sort_elements::<u128>(Ord::cmp),
+            TypedArrayKind::Float16 => {
+                self.sort_elements::<half::f16>(|a, b| match (a.is_nan(), b.is_nan()) {
+                    (false, false) => a.total_cmp(b),
+                    (nan_a, nan_b) => nan_a.cmp(&nan_b),
+                })
+            }
+            TypedArrayKind::BFloat16 => {
+                self.sort_elements::<half::bf16>(|a, b| match (a.is_nan(), b.is_nan()) {
+                    (false, false) => a.total_cmp(b),
+                    (nan_a, nan_b) => nan_a.cmp(&nan_b),
+                })
+            }
+            TypedArrayKind::Float32 => {
+                self.sort_elements::<f32>(|a, b| match (a.is_nan(), b.is_nan()) {
+                    (false, false) => a.total_cmp(b),
+                    (nan_a, nan_b) => nan_a.cmp(&nan_b),
+                })
+            }
+            TypedArrayKind::Float64 => {
+                self.sort_elements::<f64>(|a, b| match (a.is_nan(), b.is_nan()) {
+                    (false, false) => a.total_cmp(b),
+                    (nan_a, nan_b) => nan_a.cmp(&nan_b),
+                })
+            }
         }
+        Ok(())
+    }
+
+    fn sort_elements<T: TypedArrayElement>(&mut self, compare: fn(&T, &T) -> std::cmp::Ordering) {
+        let mut bytes = vec![0; self.byte_len()];
+        self._buffer.read_into(self._offset, &mut bytes);
+        let mut elements = bytes
+            .chunks(core::mem::size_of::<T>())
+            .map(|chunk| {
+                let mut element = T::Bytes::default();
+                element.as_mut().copy_from_slice(chunk);
+                T::from_ne_bytes(element)
+            })
+            .collect::<Vec<_>>();
+        elements.sort_unstable_by(compare);
+        for (chunk, element) in bytes.chunks_mut(core::mem::size_of::<T>()).zip(elements) {
+            chunk.copy_from_slice(element.to_ne_bytes().as_ref());
+        }
+        self._buffer.write_from(self._offset, &bytes);
+    }
+
+    /// Stable sort using `less(a, b)` to decide whether `a` goes before `b`,
+    /// which receives the elements as [`TypedArray::get_variant`] reads them.
+    ///
+    /// Unlike `slice::sort_by`, an inconsistent ordering never panics, and the
+    /// first error returned by `less` aborts the sort leaving the array as it
+    /// was.
+    pub fn sort_by(
+        &mut self,
+        mut less: impl FnMut(TypedArrayVariant, TypedArrayVariant) -> mlua::Result<bool>,
+    ) -> mlua::Result<()> {
+        self._buffer.check_writable()?;
+        let size = self._kind.bytes_per_element();
+        let mut bytes = vec![0; self.byte_len()];
+        self._buffer.read_into(self._offset, &mut bytes);
+        let elements = self.iter().collect::<Vec<_>>();
+
+        // Bottom-up merge sort over element indices, so the raw bytes can be
+        // permuted afterwards without converting the elements back.
+        let mut order = (0..elements.len()).collect::<Vec<_>>();
+        let mut merged = order.clone();
+        let mut width = 1;
+        while width < order.len() {
+            for start in (0..order.len()).step_by(2 * width) {
+                let middle = (start + width).min(order.len());
+                let end = (start + 2 * width).min(order.len());
+                let (mut left, mut right) = (start, middle);
+                for slot in &mut merged[start..end] {
+                    let take_right = right < end
+                        && (left >= middle || less(elements[order[right]], elements[order[left]])?);
+                    if take_right {
+                        *slot = order[right];
+                        right += 1;
+                    } else {
+                        *slot = order[left];
+                        left += 1;
+                    }
+                }
+            }
+            std::mem::swap(&mut order, &mut merged);
+            width *= 2;
+        }
+
+        // The comparator may have shrunk or detached the buffer.
+        let sorted = order
+            .iter()
+            .take(self.len())
+            .flat_map(|i| &bytes[i * size..(i + 1) * size])
+            .copied()
+            .collect::<Vec<_>>();
+        self._buffer.write_from(self._offset, &sorted);
+        Ok(())
+    }
+
+    /// Copies the elements at `indices` into a new array of the same kind.
+    fn select(&self, indices: &[usize]) -> mlua::Result<Self> {
+        let result = TypedArray::with_len(self._kind, indices.len())?;
+        let size = self._kind.bytes_per_element();
+        let mut element = vec![0; size];
+        for (i, index) in indices.iter().enumerate() {
+            self._buffer
+                .read_into(self._offset + index * size, &mut element);
+            result._buffer.write_from(i * size, &element);
+        }
+        Ok(result)
+    }
+
+    fn check_fits(&self, count: usize, index: usize) -> Result<usize, Error> {
+        index
+            .checked_sub(1)
+            .filter(|start| {
+                start
+                    .checked_add(count)
+                    .is_some_and(|end| end <= self.len())
+            })
+            .ok_or(Error::OutOfBounds {
+                target: self.name(),
+                offset: index.saturating_add(count).saturating_sub(1),
+                len: self.len(),
+            })
+    }
+
+    /// Copies the elements `begin..=end` to the 1-based `target` position
+    /// within the same array, like `table.move`.
+    pub fn copy_within(
+        &mut self,
+        target: mlua::Integer,
+        begin: Option<mlua::Integer>,
+        end: Option<mlua::Integer>,
+    ) -> mlua::Result<()> {
+        let view = self.subarray(begin, end)?;
+        self._buffer.check_writable()?;
+        let target = super::relative_range(self.len(), Some(target), None).start;
+        let count = view.len().min(self.len() - target);
+        let mut bytes = vec![0; count * self._kind.bytes_per_element()];
+        self._buffer.read_into(view._offset, &mut bytes);
+        self._buffer.write_from(
+            self._offset + target * self._kind.bytes_per_element(),
+            &bytes,
+        );
+        Ok(())
+    }
+
+    pub fn name(&self) -> &'static str {
+        self._kind.name()
     }
 
     pub fn get_number(&self, index: usize) -> Option<mlua::Number> {
-        let result = Some(match self._kind {
+        if index >= self.len() {
+            return None;
+        }
+        Some(match self._kind {
             TypedArrayKind::SInt8 => {
-                <u8 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
-                    as mlua::Number
+                <i8 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
             }
             TypedArrayKind::UInt8 => {
-                <i8 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
+                <u8 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
+            }
+            TypedArrayKind::UInt8Clamped => {
+                <ClampedU8 as TypedArrayElement>::get(&self._buffer, self._offset, index)?.0
                     as mlua::Number
             }
             TypedArrayKind::SInt16 => {
-                <u16 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
-                    as mlua::Number
+                <i16 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
             }
             TypedArrayKind::UInt16 => {
-                <i16 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
-                    as mlua::Number
+                <u16 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
             }
             TypedArrayKind::SInt32 => {
-                <u32 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
-                    as mlua::Number
+                <i32 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
             }
             TypedArrayKind::UInt32 => {
-                <i32 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
-                    as mlua::Number
+                <u32 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
             }
             TypedArrayKind::SInt64 => {
-                <u64 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
-                    as mlua::Number
+                <i64 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
             }
             TypedArrayKind::UInt64 => {
-                <i64 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
+                <u64 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
+            }
+            TypedArrayKind::SInt128 => {
+                <i128 as TypedArrayElement>::get(&self._buffer, self._offset, index)?
                     as mlua::Number
             }
-            TypedArrayKind::Float32 => {
-                <f32 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
+            TypedArrayKind::UInt128 => {
+                <u128 as TypedArrayElement>::get(&self._buffer, self._offset, index)?
                     as mlua::Number
             }
+            TypedArrayKind::Float16 => {
+                <half::f16 as TypedArrayElement>::get(&self._buffer, self._offset, index)?.into()
+            }
+            TypedArrayKind::BFloat16 => {
+                <half::bf16 as TypedArrayElement>::get(&self._buffer, self._offset, index)?.into()
+            }
+            TypedArrayKind::Float32 => {
+                <f32 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
+            }
             TypedArrayKind::Float64 => {
-                <f64 as TypedArrayElement>::get(self._buffer.slice(), self._offset, index)?
-                    as mlua::Number
+                <f64 as TypedArrayElement>::get(&self._buffer, self._offset, index)? as mlua::Number
             }
-        });
-        result
+        })
     }
 
-    pub unsafe fn unsafe_set<T: TypedArrayElement>(&mut self, index: usize, number: T) {
-        T::set(self._buffer.slice(), self._offset, index, number).unwrap_unchecked()
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self._overflow
     }
 
-    pub fn set<T: TypedArrayElement>(&mut self, index: usize, number: T) -> Result<(), ()> {
-        if self._kind != T::kind() {
-            Err(())
-        } else {
-            T::set(self._buffer.slice(), self._offset, index, number)
-        }
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self._overflow = policy;
     }
 
-    pub fn set_number(&mut self, index: usize, number: mlua::Number) -> Result<(), ()> {
-        match self._kind {
-            TypedArrayKind::SInt8 => <i8 as TypedArrayElement>::set(
-                self._buffer.slice(),
+    pub fn get_variant(&self, index: usize) -> Option<TypedArrayVariant> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(match self._kind {
+            TypedArrayKind::SInt8 => TypedArrayVariant::SInt8(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
-            ),
-            TypedArrayKind::UInt8 => <u8 as TypedArrayElement>::set(
-                self._buffer.slice(),
+            )?),
+            TypedArrayKind::UInt8 => TypedArrayVariant::UInt8(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
+            )?),
+            TypedArrayKind::UInt8Clamped => TypedArrayVariant::UInt8Clamped(
+                <ClampedU8 as TypedArrayElement>::get(&self._buffer, self._offset, index)?.0,
             ),
-            TypedArrayKind::SInt16 => <i16 as TypedArrayElement>::set(
-                self._buffer.slice(),
+            TypedArrayKind::SInt16 => TypedArrayVariant::SInt16(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
-            ),
-            TypedArrayKind::UInt16 => <u16 as TypedArrayElement>::set(
-                self._buffer.slice(),
+            )?),
+            TypedArrayKind::UInt16 => TypedArrayVariant::UInt16(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
-            ),
-            TypedArrayKind::SInt32 => <i32 as TypedArrayElement>::set(
-                self._buffer.slice(),
+            )?),
+            TypedArrayKind::SInt32 => TypedArrayVariant::SInt32(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
-            ),
-            TypedArrayKind::UInt32 => <u32 as TypedArrayElement>::set(
-                self._buffer.slice(),
+            )?),
+            TypedArrayKind::UInt32 => TypedArrayVariant::UInt32(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
-            ),
-            TypedArrayKind::SInt64 => <i64 as TypedArrayElement>::set(
-                self._buffer.slice(),
+            )?),
+            TypedArrayKind::SInt64 => TypedArrayVariant::SInt64(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
-            ),
-            TypedArrayKind::UInt64 => <u64 as TypedArrayElement>::set(
-                self._buffer.slice(),
+            )?),
+            TypedArrayKind::UInt64 => TypedArrayVariant::UInt64(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
-            ),
-            TypedArrayKind::Float32 => <f32 as TypedArrayElement>::set(
-                self._buffer.slice(),
+            )?),
+            TypedArrayKind::SInt128 => TypedArrayVariant::SInt128(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
-            ),
-            TypedArrayKind::Float64 => <f64 as TypedArrayElement>::set(
-                self._buffer.slice(),
+            )?),
+            TypedArrayKind::UInt128 => TypedArrayVariant::UInt128(TypedArrayElement::get(
+                &self._buffer,
                 self._offset,
                 index,
-                number as _,
-            ),
+            )?),
+            TypedArrayKind::Float16 => TypedArrayVariant::Float16(TypedArrayElement::get(
+                &self._buffer,
+                self._offset,
+                index,
+            )?),
+            TypedArrayKind::BFloat16 => TypedArrayVariant::BFloat16(TypedArrayElement::get(
+                &self._buffer,
+                self._offset,
+                index,
+            )?),
+            TypedArrayKind::Float32 => TypedArrayVariant::Float32(TypedArrayElement::get(
+                &self._buffer,
+                self._offset,
+                index,
+            )?),
+            TypedArrayKind::Float64 => TypedArrayVariant::Float64(TypedArrayElement::get(
+                &self._buffer,
+                self._offset,
+                index,
+            )?),
+        })
+    }
+
+    /// Stores `value` converting it to the element type, see
+    /// [`TypedArray::encode`].
+    pub fn set_value(&mut self, index: usize, value: Numeric) -> Result<(), Error> {
+        let out_of_bounds = Error::OutOfBounds {
+            target: self.name(),
+            offset: index + 1,
+            len: self.len(),
+        };
+        if index >= self.len() {
+            return Err(out_of_bounds);
         }
+        self._buffer.check_writable()?;
+        let size = self._kind.bytes_per_element();
+        let bytes = self.encode(value)?;
+        self._buffer
+            .write_from(self._offset + index * size, &bytes[..size])
+            .ok_or(out_of_bounds)
     }
 
+    /// Converts `value` to the element type and returns its native-endian
+    /// bytes, which fill the first `bytes_per_element` bytes of the result.
+    /// Integer kinds apply the array's [`OverflowPolicy`] to values they
+    /// cannot represent.
+    ///
+    /// With the default wrap policy, negative integers assigned to a
+    /// `UInt64Array` or a `UInt128Array` are taken as their bit pattern so that
+    /// values read from it round-trip; the other policies reject or clamp them
+    /// like any other out-of-range value. A `UInt8ClampedArray` always
+    /// saturates regardless of the policy.
+    fn encode(&self, value: Numeric) -> Result<[u8; 16], Error> {
+        fn bytes<T: TypedArrayElement>(element: T) -> [u8; 16] {
+            let mut bytes = [0; 16];
+            let element = element.to_ne_bytes();
+            bytes[..element.as_ref().len()].copy_from_slice(element.as_ref());
+            bytes
+        }
+        let overflow = Error::Overflow {
+            target: self.name(),
+        };
+        macro_rules! integer {
+            ($type:ty) => {{
+                let wide = value
+                    .to_integer(<$type>::MIN.into(), <$type>::MAX.into(), self._overflow)
+                    .ok_or(overflow)?;
+                bytes(wide as $type)
+            }};
+        }
+        Ok(match (self._kind, value) {
+            (TypedArrayKind::SInt8, _) => integer!(i8),
+            (TypedArrayKind::UInt8, _) => integer!(u8),
+            (TypedArrayKind::UInt8Clamped, value) => bytes(ClampedU8::from(value)),
+            (TypedArrayKind::SInt16, _) => integer!(i16),
+            (TypedArrayKind::UInt16, _) => integer!(u16),
+            (TypedArrayKind::SInt32, _) => integer!(i32),
+            (TypedArrayKind::UInt32, _) => integer!(u32),
+            (TypedArrayKind::SInt64, _) => integer!(i64),
+            (TypedArrayKind::UInt64, _) => integer!(u64),
+            (TypedArrayKind::SInt128, _) => integer!(i128),
+            (TypedArrayKind::UInt128, Numeric::WideUnsigned(v)) => bytes(v),
+            // Values in the upper half of the range do not fit the i128 that
+            // `to_integer` works with, so floats are checked here unless they
+            // wrap.
+            (TypedArrayKind::UInt128, Numeric::Number(v))
+                if self._overflow != OverflowPolicy::Wrap && !v.is_nan() =>
+            {
+                let v = v.trunc();
+                if (0.0..2f64.powi(128)).contains(&v) {
+                    bytes(v as u128)
+                } else if self._overflow == OverflowPolicy::Clamp {
+                    bytes(if v < 0.0 { 0 } else { u128::MAX })
+                } else {
+                    return Err(overflow);
+                }
+            }
+            (TypedArrayKind::UInt128, value) => {
+                let wide = value
+                    .to_integer(0, i128::MAX, self._overflow)
+                    .ok_or(overflow)?;
+                bytes(wide as u128)
+            }
+            // Narrowed straight from f64 so that rounding happens only once.
+            (TypedArrayKind::Float16, value) => bytes(half::f16::from_f64(value.to_number())),
+            (TypedArrayKind::BFloat16, value) => bytes(half::bf16::from_f64(value.to_number())),
+            (TypedArrayKind::Float32, value) => bytes(value.to_number() as f32),
+            (TypedArrayKind::Float64, value) => bytes(value.to_number()),
+        })
+    }
+
+    /// # Safety
+    /// `index` must be in bounds, `T` must match the kind of the array and its
+    /// buffer must not be mapped read-only.
+    pub unsafe fn unsafe_set<T: TypedArrayElement>(&mut self, index: usize, number: T) {
+        T::set(&self._buffer, self._offset, index, number).unwrap_unchecked()
+    }
+
+    pub fn set<T: TypedArrayElement>(&mut self, index: usize, number: T) -> Result<(), Error> {
+        if self._kind != T::kind() {
+            return Err(Error::KindMismatch {
+                expected: self.name(),
+                found: T::kind().name(),
+            });
+        }
+        let out_of_bounds = Error::OutOfBounds {
+            target: self.name(),
+            offset: index + 1,
+            len: self.len(),
+        };
+        if index >= self.len() {
+            return Err(out_of_bounds);
+        }
+        self._buffer.check_writable()?;
+        T::set(&self._buffer, self._offset, index, number).ok_or(out_of_bounds)
+    }
+
+    pub fn set_number(&mut self, index: usize, number: mlua::Number) -> Result<(), Error> {
+        self.set_value(index, Numeric::Number(number))
+    }
+
+    /// # Safety
+    /// `index` must be in bounds and `T` must match the kind of the array.
     pub unsafe fn unsafe_get<T: TypedArrayElement>(&self, index: usize) -> T {
-        T::get(self._buffer.slice(), self._offset, index).unwrap_unchecked()
+        T::get(&self._buffer, self._offset, index).unwrap_unchecked()
     }
 
     pub fn get<T: TypedArrayElement>(&self, index: usize) -> Option<T> {
-        if T::kind() != self._kind {
+        if T::kind() != self._kind || index >= self.len() {
             return None;
         };
-        T::get(self._buffer.slice(), self._offset, index)
+        T::get(&self._buffer, self._offset, index)
     }
 
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = TypedArrayVariant> + 'a {
-        (0..self.len()).map(|i| match self._kind {
-            TypedArrayKind::SInt8 => TypedArrayVariant::SInt8(unsafe { self.unsafe_get::<i8>(i) }),
-            TypedArrayKind::UInt8 => TypedArrayVariant::UInt8(unsafe { self.unsafe_get::<u8>(i) }),
-            TypedArrayKind::SInt16 => {
-                TypedArrayVariant::SInt16(unsafe { self.unsafe_get::<i16>(i) })
-            }
-            TypedArrayKind::UInt16 => {
-                TypedArrayVariant::UInt16(unsafe { self.unsafe_get::<u16>(i) })
-            }
-            TypedArrayKind::SInt32 => {
-                TypedArrayVariant::SInt32(unsafe { self.get::<i32>(i).unwrap_unchecked() })
-            }
-            TypedArrayKind::UInt32 => {
-                TypedArrayVariant::UInt32(unsafe { self.unsafe_get::<u32>(i) })
-            }
-            TypedArrayKind::SInt64 => {
-                TypedArrayVariant::SInt64(unsafe { self.unsafe_get::<i64>(i) })
+    /// Copies the bytes covered by the array.
+    fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        self._buffer.check_attached()?;
+        let mut bytes = vec![0; self.byte_len()];
+        self._buffer.read_into(self._offset, &mut bytes);
+        Ok(bytes)
+    }
+
+    fn check_kind<T: TypedArrayElement>(&self) -> Result<(), Error> {
+        if T::kind() == self._kind {
+            Ok(())
+        } else {
+            Err(Error::KindMismatch {
+                expected: T::kind().name(),
+                found: self.name(),
+            })
+        }
+    }
+
+    /// The bytes of the buffer visible through the view, empty once a
+    /// fixed-length view no longer fits in a shrunk buffer.
+    fn byte_range(&self) -> std::ops::Range<usize> {
+        let start = self._offset.min(self._buffer.len());
+        start..start + self.byte_len()
+    }
+
+    /// Borrows the elements as `T`, checking the kind only once, for host code
+    /// that processes whole arrays.
+    ///
+    /// The kind must match exactly, so a `UInt8ClampedArray` is borrowed as
+    /// [`ClampedU8`], not `u8`. A view over a buffer shrunk below its end
+    /// borrows no elements.
+    pub fn as_typed<T: TypedArrayElement>(&self) -> Result<TypedArrayRef<'_, T>, Error> {
+        self.check_kind::<T>()?;
+        self._buffer.check_attached()?;
+        let range = self.byte_range();
+        Ok(match &self._buffer {
+            super::ArrayBufferLike::ArrayBuffer(buffer) => {
+                TypedArrayRef::local(std::cell::Ref::map(buffer.bytes(), |bytes| &bytes[range]))
             }
-            TypedArrayKind::UInt64 => {
-                TypedArrayVariant::UInt64(unsafe { self.unsafe_get::<u64>(i) })
+            super::ArrayBufferLike::SharedArrayBuffer(buffer) => {
+                TypedArrayRef::shared(SharedBytes::new(buffer, range))
             }
-            TypedArrayKind::Float32 => {
-                TypedArrayVariant::Float32(unsafe { self.unsafe_get::<f32>(i) })
+        })
+    }
+
+    /// Mutable counterpart of [`TypedArray::as_typed`].
+    pub fn as_typed_mut<T: TypedArrayElement>(&mut self) -> Result<TypedArrayRefMut<'_, T>, Error> {
+        self.check_kind::<T>()?;
+        self._buffer.check_writable()?;
+        let range = self.byte_range();
+        Ok(match &self._buffer {
+            super::ArrayBufferLike::ArrayBuffer(buffer) => {
+                TypedArrayRefMut::local(std::cell::RefMut::map(buffer.bytes_mut()?, |bytes| {
+                    &mut bytes[range]
+                }))
             }
-            TypedArrayKind::Float64 => {
-                TypedArrayVariant::Float64(unsafe { self.unsafe_get::<f64>(i) })
+            super::ArrayBufferLike::SharedArrayBuffer(buffer) => {
+                TypedArrayRefMut::shared(SharedBytes::new(buffer, range))
             }
         })
     }
+
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = TypedArrayVariant> + 'a {
+        (0..self.len()).map_while(|i| self.get_variant(i))
+    }
 }
 
 impl std::fmt::Display for TypedArray {
@@ -477,6 +1339,112 @@ impl std::fmt::Display for TypedArray {
     }
 }
 
+/// What each step of an [`iterator`] yields.
+#[derive(Debug, Clone, Copy)]
+enum Iteration {
+    Entries,
+    Keys,
+    Values,
+}
+
+/// Creates a stateful Lua iterator over the elements of `array`, usable
+/// directly in a generic `for`.
+///
+/// The length is checked on every step, so the loop ends early if a
+/// resizable buffer shrinks and raises an error if it gets detached.
+fn iterator<'lua>(
+    lua: &'lua mlua::Lua,
+    array: TypedArray,
+    iteration: Iteration,
+) -> mlua::Result<mlua::Function<'lua>> {
+    let next = std::cell::Cell::new(0);
+    lua.create_function(move |lua, ()| {
+        array._buffer.check_attached()?;
+        let index = next.get();
+        let value = match array.get_variant(index) {
+            Some(value) => value,
+            None => return Ok(mlua::MultiValue::new()),
+        };
+        next.set(index + 1);
+        match iteration {
+            Iteration::Entries => mlua::ToLuaMulti::to_lua_multi((index + 1, value), lua),
+            Iteration::Keys => mlua::ToLuaMulti::to_lua_multi(index + 1, lua),
+            Iteration::Values => mlua::ToLuaMulti::to_lua_multi(value, lua),
+        }
+    })
+}
+
+/// Calls `callback(value, index, array)` on each element, in reverse if
+/// `backwards`, until `stop` accepts the truthiness of its result.
+///
+/// Returns the 0-based index and value of the element it stopped at.
+fn find(
+    this: &mlua::AnyUserData,
+    callback: mlua::Function,
+    backwards: bool,
+    stop: impl Fn(bool) -> bool,
+) -> mlua::Result<Option<(usize, Option<TypedArrayVariant>)>> {
+    let array = this.borrow::<TypedArray>()?.clone();
+    let indices: Box<dyn Iterator<Item = usize>> = if backwards {
+        Box::new((0..array.len()).rev())
+    } else {
+        Box::new(0..array.len())
+    };
+    for i in indices {
+        let value = array.get_variant(i);
+        if stop(callback.call::<_, bool>((value, i + 1, this.clone()))?) {
+            return Ok(Some((i, value)));
+        }
+    }
+    Ok(None)
+}
+
+/// Folds the elements at `indices` with `callback(accumulator, value, index,
+/// array)`, starting from `initial` or the first visited element.
+fn reduce<'lua>(
+    lua: &'lua mlua::Lua,
+    this: &mlua::AnyUserData<'lua>,
+    array: &TypedArray,
+    callback: mlua::Function<'lua>,
+    initial: Option<mlua::Value<'lua>>,
+    mut indices: impl Iterator<Item = usize>,
+) -> mlua::Result<mlua::Value<'lua>> {
+    let mut accumulator = match initial {
+        Some(initial) => initial,
+        None => match indices.next() {
+            Some(i) => mlua::ToLua::to_lua(array.get_variant(i), lua)?,
+            None => {
+                return Err(mlua::Error::RuntimeError(
+                    "reduce of empty array with no initial value".into(),
+                ))
+            }
+        },
+    };
+    for i in indices {
+        accumulator = callback.call((accumulator, array.get_variant(i), i + 1, this.clone()))?;
+    }
+    Ok(accumulator)
+}
+
+/// Sorts natively, or with `comparator(a, b)` which may either return a
+/// number like in JS or a boolean like in `table.sort`.
+fn sort(array: &mut TypedArray, comparator: Option<mlua::Function>) -> mlua::Result<()> {
+    match comparator {
+        None => Ok(array.sort()?),
+        Some(comparator) => array.sort_by(|a, b| match comparator.call((a, b))? {
+            mlua::Value::Boolean(less) => Ok(less),
+            mlua::Value::Integer(order) => Ok(order < 0),
+            mlua::Value::Number(order) => Ok(order < 0.0),
+            mlua::Value::Nil => Ok(false),
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "number",
+                message: Some("comparator must return a number or a boolean".into()),
+            }),
+        }),
+    }
+}
+
 impl mlua::UserData for TypedArray {
     fn add_fields<'lua, F: mlua::UserDataFields<'lua, Self>>(fields: &mut F) {
         fields.add_field_method_get("name", |_, this| Ok(this.name()));
@@ -486,6 +1454,11 @@ impl mlua::UserData for TypedArray {
         fields.add_field_method_get("buffer", |_, this| Ok(this._buffer.clone()));
         fields.add_field_method_get("byteLength", |_, this| Ok(this.byte_len()));
         fields.add_field_method_get("byteOffset", |_, this| Ok(this.byte_offset()));
+        fields.add_field_method_get("overflow", |_, this| Ok(this._overflow.name()));
+        fields.add_field_method_set("overflow", |_, this, name: String| {
+            this._overflow = OverflowPolicy::parse(&name)?;
+            Ok(())
+        });
     }
 
     fn add_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(methods: &mut M) {
@@ -497,38 +1470,291 @@ impl mlua::UserData for TypedArray {
             mlua::MetaMethod::Len,
             |_, this: TypedArray| -> Result<usize, _> { Ok(this.len()) },
         );
+        methods.add_method(
+            "subarray",
+            |_, this, args: (Option<mlua::Integer>, Option<mlua::Integer>)| {
+                let (begin, end) = args;
+                Ok(this.subarray(begin, end)?)
+            },
+        );
+        methods.add_method(
+            "slice",
+            |_, this, args: (Option<mlua::Integer>, Option<mlua::Integer>)| {
+                let (begin, end) = args;
+                this.slice(begin, end)
+            },
+        );
+        methods.add_function(
+            "fill",
+            |_,
+             args: (
+                mlua::AnyUserData,
+                Numeric,
+                Option<mlua::Integer>,
+                Option<mlua::Integer>,
+            )| {
+                let (this, value, begin, end) = args;
+                this.borrow_mut::<TypedArray>()?.fill(value, begin, end)?;
+                Ok(this)
+            },
+        );
+        methods.add_function(
+            "set",
+            |_, args: (TypedArray, mlua::Value, Option<usize>)| -> Result<(), _> {
+                // `this` is a cheap handle to the view, so the source may be
+                // the very same userdata without conflicting borrows.
+                let (mut this, source, index) = args;
+                let index = index.unwrap_or(1);
+                match source {
+                    mlua::Value::UserData(ud) if ud.is::<TypedArray>() => {
+                        let source = ud.borrow::<TypedArray>()?.clone();
+                        this.set_from(&source, index)
+                    }
+                    mlua::Value::Table(table) => {
+                        let values = table
+                            .sequence_values::<Numeric>()
+                            .collect::<mlua::Result<Vec<_>>>()?;
+                        this.set_from_values(&values, index)
+                    }
+                    source => Err(mlua::Error::FromLuaConversionError {
+                        from: source.type_name(),
+                        to: "TypedArray",
+                        message: Some("expected a typed array or a sequence table".into()),
+                    }),
+                }
+            },
+        );
+        methods.add_function(
+            "copyWithin",
+            |_,
+             args: (
+                mlua::AnyUserData,
+                mlua::Integer,
+                Option<mlua::Integer>,
+                Option<mlua::Integer>,
+            )| {
+                let (this, target, begin, end) = args;
+                this.borrow_mut::<TypedArray>()?
+                    .copy_within(target, begin, end)?;
+                Ok(this)
+            },
+        );
+        methods.add_function(
+            "forEach",
+            |_, args: (mlua::AnyUserData, mlua::Function)| -> Result<(), _> {
+                let (this, callback) = args;
+                let array = this.borrow::<TypedArray>()?.clone();
+                for i in 0..array.len() {
+                    callback.call::<_, ()>((array.get_variant(i), i + 1, this.clone()))?;
+                }
+                Ok(())
+            },
+        );
+        methods.add_function(
+            "map",
+            |_, args: (mlua::AnyUserData, mlua::Function)| -> Result<TypedArray, _> {
+                let (this, callback) = args;
+                let array = this.borrow::<TypedArray>()?.clone();
+                let mut result = TypedArray::with_len(array._kind, array.len())?;
+                result._overflow = array._overflow;
+                for i in 0..array.len() {
+                    let value =
+                        callback.call::<_, Numeric>((array.get_variant(i), i + 1, this.clone()))?;
+                    result.set_value(i, value)?;
+                }
+                Ok(result)
+            },
+        );
+        methods.add_function(
+            "filter",
+            |_, args: (mlua::AnyUserData, mlua::Function)| -> Result<TypedArray, _> {
+                let (this, callback) = args;
+                let array = this.borrow::<TypedArray>()?.clone();
+                let mut selected = Vec::new();
+                for i in 0..array.len() {
+                    if callback.call::<_, bool>((array.get_variant(i), i + 1, this.clone()))? {
+                        selected.push(i);
+                    }
+                }
+                array.select(&selected)
+            },
+        );
+        methods.add_function(
+            "reduce",
+            |lua, args: (mlua::AnyUserData, mlua::Function, Option<mlua::Value>)| {
+                let (this, callback, initial) = args;
+                let array = this.borrow::<TypedArray>()?.clone();
+                reduce(lua, &this, &array, callback, initial, 0..array.len())
+            },
+        );
+        methods.add_function(
+            "reduceRight",
+            |lua, args: (mlua::AnyUserData, mlua::Function, Option<mlua::Value>)| {
+                let (this, callback, initial) = args;
+                let array = this.borrow::<TypedArray>()?.clone();
+                reduce(
+                    lua,
+                    &this,
+                    &array,
+                    callback,
+                    initial,
+                    (0..array.len()).rev(),
+                )
+            },
+        );
+        methods.add_function(
+            "every",
+            |_, args: (mlua::AnyUserData, mlua::Function)| -> Result<bool, _> {
+                let (this, callback) = args;
+                Ok(find(&this, callback, false, |matched| !matched)?.is_none())
+            },
+        );
+        methods.add_function(
+            "some",
+            |_, args: (mlua::AnyUserData, mlua::Function)| -> Result<bool, _> {
+                let (this, callback) = args;
+                Ok(find(&this, callback, false, |matched| matched)?.is_some())
+            },
+        );
+        methods.add_function(
+            "find",
+            |_,
+             args: (mlua::AnyUserData, mlua::Function)|
+             -> Result<Option<TypedArrayVariant>, _> {
+                let (this, callback) = args;
+                Ok(find(&this, callback, false, |matched| matched)?.and_then(|(_, x)| x))
+            },
+        );
+        methods.add_function(
+            "findIndex",
+            |_, args: (mlua::AnyUserData, mlua::Function)| -> Result<Option<usize>, _> {
+                let (this, callback) = args;
+                Ok(find(&this, callback, false, |matched| matched)?.map(|(i, _)| i + 1))
+            },
+        );
+        methods.add_function(
+            "findLast",
+            |_,
+             args: (mlua::AnyUserData, mlua::Function)|
+             -> Result<Option<TypedArrayVariant>, _> {
+                let (this, callback) = args;
+                Ok(find(&this, callback, true, |matched| matched)?.and_then(|(_, x)| x))
+            },
+        );
+        methods.add_method(
+            "indexOf",
+            |_, this, args: (Numeric, Option<mlua::Integer>)| {
+                let (value, from) = args;
+                Ok(this.index_of(value, from))
+            },
+        );
+        methods.add_method(
+            "lastIndexOf",
+            |_, this, args: (Numeric, Option<mlua::Integer>)| {
+                let (value, from) = args;
+                Ok(this.last_index_of(value, from))
+            },
+        );
+        methods.add_method(
+            "includes",
+            |_, this, args: (Numeric, Option<mlua::Integer>)| {
+                let (value, from) = args;
+                Ok(this.includes(value, from))
+            },
+        );
+        methods.add_method(
+            "toString",
+            |lua, this, args: (Option<usize>, Option<usize>)| {
+                let (offset, length) = args;
+                this._buffer.check_attached()?;
+                let range = super::byte_range(this.name(), this.byte_len(), offset, length)?;
+                let start = this.byte_range().start;
+                this._buffer
+                    .to_lua_string(lua, start + range.start..start + range.end)
+            },
+        );
+        methods.add_method("toBase64", |_, this, options: Option<mlua::Table>| {
+            this.check_kind::<u8>()?;
+            let options = super::base64::Options::from_table(options)?;
+            Ok(super::base64::encode(&this.to_bytes()?, options))
+        });
+        methods.add_method("toHex", |_, this, ()| {
+            this.check_kind::<u8>()?;
+            Ok(super::base64::encode_hex(&this.to_bytes()?))
+        });
+        methods.add_method(
+            "setFromBase64",
+            |_, this, args: (mlua::String, Option<mlua::Table>)| {
+                let (s, options) = args;
+                this.check_kind::<u8>()?;
+                this._buffer.check_writable()?;
+                let options = super::base64::Options::from_table(options)?;
+                Ok(super::base64::decode(s.as_bytes(), options, this.len()).write_into(this)?)
+            },
+        );
+        methods.add_method("setFromHex", |_, this, s: mlua::String| {
+            this.check_kind::<u8>()?;
+            this._buffer.check_writable()?;
+            Ok(super::base64::decode_hex(s.as_bytes(), this.len()).write_into(this)?)
+        });
+        methods.add_method("join", |_, this, separator: Option<String>| {
+            Ok(this.join(separator.as_deref().unwrap_or(",")))
+        });
+        methods.add_function("reverse", |_, this: mlua::AnyUserData| {
+            this.borrow_mut::<TypedArray>()?.reverse()?;
+            Ok(this)
+        });
+        methods.add_function(
+            "sort",
+            |_, args: (mlua::AnyUserData, Option<mlua::Function>)| {
+                let (this, comparator) = args;
+                let mut array = this.borrow::<TypedArray>()?.clone();
+                sort(&mut array, comparator)?;
+                Ok(this)
+            },
+        );
+        methods.add_method(
+            "toSorted",
+            |_, this, comparator: Option<mlua::Function>| -> Result<TypedArray, _> {
+                let mut array = this.slice(None, None)?;
+                sort(&mut array, comparator)?;
+                Ok(array)
+            },
+        );
         methods.add_meta_function(
             mlua::MetaMethod::Index,
-            |_, args: (TypedArray, usize)| -> Result<Option<mlua::Number>, _> {
+            |_, args: (TypedArray, usize)| -> Result<Option<TypedArrayVariant>, _> {
                 let (this, index) = args;
+                this._buffer.check_attached()?;
                 Ok(index
                     .checked_sub(1)
-                    .and_then(|index| this.get_number(index)))
+                    .and_then(|index| this.get_variant(index)))
             },
         );
         methods.add_meta_function(
             mlua::MetaMethod::NewIndex,
-            |_, args: (TypedArray, usize, mlua::Number)| -> Result<(), _> {
-                let (mut this, index, number) = args;
-                index
-                    .checked_sub(1)
-                    .and_then(move |index| this.set_number(index, number).ok())
-                    .ok_or_else(|| {
-                        mlua::Error::RuntimeError("assignment index out of range".into())
-                    })
+            |_, args: (TypedArray, usize, Numeric)| -> Result<(), _> {
+                let (mut this, index, value) = args;
+                this._buffer.check_attached()?;
+                let index = index.checked_sub(1).ok_or(Error::OutOfBounds {
+                    target: this.name(),
+                    offset: 0,
+                    len: this.len(),
+                })?;
+                Ok(this.set_value(index, value)?)
             },
         );
-        // methods.add_meta_function(
-        //     mlua::MetaMethod::Iter,
-        //     |_, args: (TypedArray, usize, mlua::Number)| -> Result<(), _> {
-        //         let (mut this, index, number) = args;
-        //         index
-        //             .checked_sub(1)
-        //             .and_then(move |index| this.set_number(index, number).ok())
-        //             .ok_or_else(|| {
-        //                 mlua::Error::RuntimeError("assignment index out of range".into())
-        //             })
-        //     },
-        // );
+        methods.add_meta_method(mlua::MetaMethod::Pairs, |lua, this, ()| {
+            iterator(lua, this.clone(), Iteration::Entries)
+        });
+        methods.add_method("entries", |lua, this, ()| {
+            iterator(lua, this.clone(), Iteration::Entries)
+        });
+        methods.add_method("keys", |lua, this, ()| {
+            iterator(lua, this.clone(), Iteration::Keys)
+        });
+        methods.add_method("values", |lua, this, ()| {
+            iterator(lua, this.clone(), Iteration::Values)
+        });
     }
 }
@@ -0,0 +1,276 @@
+use std::cell::{Ref, RefMut};
+use std::marker::PhantomData;
+use std::slice::ChunksExact;
+
+use super::typed_array::TypedArrayElement;
+use super::SharedArrayBuffer;
+
+/// A range of the bytes of a `SharedArrayBuffer`, read and written through
+/// its `read_into` and `write_from` like every other access to it.
+#[derive(Clone, Copy)]
+pub(super) struct SharedBytes<'a> {
+    buffer: &'a SharedArrayBuffer,
+    offset: usize,
+    len: usize,
+}
+
+impl<'a> SharedBytes<'a> {
+    pub(super) fn new(buffer: &'a SharedArrayBuffer, range: std::ops::Range<usize>) -> Self {
+        SharedBytes {
+            buffer,
+            offset: range.start,
+            len: range.len(),
+        }
+    }
+
+    fn load<T: TypedArrayElement>(self, index: usize) -> Option<T> {
+        let range = element_range::<T>(index).filter(|range| range.end <= self.len)?;
+        let mut bytes = T::Bytes::default();
+        self.buffer
+            .read_into(self.offset + range.start, bytes.as_mut())?;
+        Some(T::from_ne_bytes(bytes))
+    }
+
+    fn store<T: TypedArrayElement>(self, index: usize, value: T) -> Option<()> {
+        let range = element_range::<T>(index).filter(|range| range.end <= self.len)?;
+        self.buffer
+            .write_from(self.offset + range.start, value.to_ne_bytes().as_ref())
+    }
+}
+
+/// The bytes of a view, borrowed from either kind of buffer.
+#[derive(Clone, Copy)]
+enum Bytes<'a> {
+    Local(&'a [u8]),
+    Shared(SharedBytes<'a>),
+}
+
+impl<'a> Bytes<'a> {
+    fn len(self) -> usize {
+        match self {
+            Bytes::Local(bytes) => bytes.len(),
+            Bytes::Shared(bytes) => bytes.len,
+        }
+    }
+
+    fn get<T: TypedArrayElement>(self, index: usize) -> Option<T> {
+        match self {
+            Bytes::Local(bytes) => Some(load(bytes.get(element_range::<T>(index)?)?)),
+            Bytes::Shared(bytes) => bytes.load(index),
+        }
+    }
+
+    fn iter<T: TypedArrayElement>(self) -> Iter<'a, T> {
+        let size = core::mem::size_of::<T>();
+        Iter {
+            _chunks: match self {
+                Bytes::Local(bytes) => Chunks::Local(bytes.chunks_exact(size)),
+                Bytes::Shared(bytes) => Chunks::Shared(bytes, 0..bytes.len / size),
+            },
+            _marker: PhantomData,
+        }
+    }
+}
+
+fn element_range<T>(index: usize) -> Option<std::ops::Range<usize>> {
+    let size = core::mem::size_of::<T>();
+    let start = index.checked_mul(size)?;
+    Some(start..start.checked_add(size)?)
+}
+
+fn load<T: TypedArrayElement>(chunk: &[u8]) -> T {
+    let mut bytes = T::Bytes::default();
+    bytes.as_mut().copy_from_slice(chunk);
+    T::from_ne_bytes(bytes)
+}
+
+enum Chunks<'a> {
+    Local(ChunksExact<'a, u8>),
+    /// The indices of the elements left.
+    Shared(SharedBytes<'a>, std::ops::Range<usize>),
+}
+
+/// Iterator over the elements of a [`TypedArrayRef`] or a
+/// [`TypedArrayRefMut`], by value.
+pub struct Iter<'a, T> {
+    _chunks: Chunks<'a>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: TypedArrayElement> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match &mut self._chunks {
+            Chunks::Local(chunks) => chunks.next().map(load),
+            Chunks::Shared(bytes, indices) => indices.next().and_then(|index| bytes.load(index)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self._chunks {
+            Chunks::Local(chunks) => chunks.size_hint(),
+            Chunks::Shared(_, indices) => indices.size_hint(),
+        }
+    }
+}
+
+impl<T: TypedArrayElement> ExactSizeIterator for Iter<'_, T> {}
+
+enum LocalOrShared<L, S> {
+    Local(L),
+    Shared(S),
+}
+
+/// Read access to the elements of a typed array known to hold `T`, obtained
+/// with `TypedArray::as_typed`.
+///
+/// The kind is checked once when the view is created, so element accesses
+/// compile down to plain loads. Elements are returned by value because they
+/// are not necessarily aligned within the buffer.
+///
+/// The buffer stays borrowed while the view is alive: Lua code writing to,
+/// resizing or detaching it in the meantime panics.
+pub struct TypedArrayRef<'a, T> {
+    _bytes: LocalOrShared<Ref<'a, [u8]>, SharedBytes<'a>>,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: TypedArrayElement> TypedArrayRef<'a, T> {
+    pub(super) fn local(bytes: Ref<'a, [u8]>) -> Self {
+        TypedArrayRef {
+            _bytes: LocalOrShared::Local(bytes),
+            _marker: PhantomData,
+        }
+    }
+
+    pub(super) fn shared(bytes: SharedBytes<'a>) -> Self {
+        TypedArrayRef {
+            _bytes: LocalOrShared::Shared(bytes),
+            _marker: PhantomData,
+        }
+    }
+
+    fn bytes(&self) -> Bytes<'_> {
+        match &self._bytes {
+            LocalOrShared::Local(bytes) => Bytes::Local(bytes),
+            LocalOrShared::Shared(bytes) => Bytes::Shared(*bytes),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes().len() / core::mem::size_of::<T>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the element at the 0-based `index`, or `None` if out of bounds.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.bytes().get(index)
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.bytes().iter()
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+}
+
+/// Read and write access to the elements of a typed array known to hold `T`,
+/// obtained with `TypedArray::as_typed_mut`.
+///
+/// The buffer stays mutably borrowed while the view is alive: Lua code
+/// accessing it in the meantime panics.
+pub struct TypedArrayRefMut<'a, T> {
+    _bytes: LocalOrShared<RefMut<'a, [u8]>, SharedBytes<'a>>,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: TypedArrayElement> TypedArrayRefMut<'a, T> {
+    pub(super) fn local(bytes: RefMut<'a, [u8]>) -> Self {
+        TypedArrayRefMut {
+            _bytes: LocalOrShared::Local(bytes),
+            _marker: PhantomData,
+        }
+    }
+
+    pub(super) fn shared(bytes: SharedBytes<'a>) -> Self {
+        TypedArrayRefMut {
+            _bytes: LocalOrShared::Shared(bytes),
+            _marker: PhantomData,
+        }
+    }
+
+    fn bytes(&self) -> Bytes<'_> {
+        match &self._bytes {
+            LocalOrShared::Local(bytes) => Bytes::Local(bytes),
+            LocalOrShared::Shared(bytes) => Bytes::Shared(*bytes),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes().len() / core::mem::size_of::<T>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the element at the 0-based `index`, or `None` if out of bounds.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.bytes().get(index)
+    }
+
+    /// Stores `value` at the 0-based `index`, or returns `None` without
+    /// writing anything if out of bounds.
+    pub fn set(&mut self, index: usize, value: T) -> Option<()> {
+        match &mut self._bytes {
+            LocalOrShared::Local(bytes) => bytes
+                .get_mut(element_range::<T>(index)?)?
+                .copy_from_slice(value.to_ne_bytes().as_ref()),
+            LocalOrShared::Shared(bytes) => bytes.store(index, value)?,
+        }
+        Some(())
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.bytes().iter()
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+
+    /// Overwrites every element with those of `src`.
+    ///
+    /// # Panics
+    /// If `src` does not have the same length as the view, like
+    /// `slice::copy_from_slice`.
+    pub fn copy_from_slice(&mut self, src: &[T])
+    where
+        T: Copy,
+    {
+        assert_eq!(
+            self.len(),
+            src.len(),
+            "source slice length does not match the typed array length"
+        );
+        let size = core::mem::size_of::<T>();
+        match &mut self._bytes {
+            LocalOrShared::Local(bytes) => {
+                for (chunk, value) in bytes.chunks_exact_mut(size).zip(src) {
+                    chunk.copy_from_slice(value.to_ne_bytes().as_ref());
+                }
+            }
+            LocalOrShared::Shared(bytes) => {
+                for (index, value) in src.iter().enumerate() {
+                    bytes.store(index, *value);
+                }
+            }
+        }
+    }
+}
@@ -2,7 +2,7 @@ extern crate mlua;
 
 pub mod lua;
 
-fn exec<'lua>(lua: &'lua mlua::Lua) {
+fn exec(lua: &mlua::Lua) {
     let result = || -> mlua::Result<()> {
         let chunk = mlua::chunk! {
             local function toUTF32(s)